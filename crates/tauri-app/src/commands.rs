@@ -44,10 +44,10 @@ pub fn get_timeline_info(path: String) -> Result<TimelineInfo, String> {
     let timeline: timeline_core::Timeline = serde_json::from_str(&content)
         .map_err(|e| format!("Failed to parse timeline: {}", e))?;
     
-    let clip_count: usize = timeline.tracks.iter().map(|t| t.clips.len()).sum();
+    let clip_count: usize = timeline.tracks.iter().map(|t| t.clips().len()).sum();
     
     Ok(TimelineInfo {
-        duration: timeline.duration.as_secs_f64(),
+        duration: timeline.duration().to_seconds(),
         track_count: timeline.tracks.len(),
         clip_count,
     })
@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{AnimatedValue, Clip, RationalTime, Timeline, TimelineError, Track, TrackKind};
+
+/// Shape of a fade, described as it rises from silence to full level.
+/// Fade-outs play the same shape backwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Keeps perceived loudness steady when crossfading two sources.
+    EqualPower,
+    /// Rises quickly, then levels off.
+    Logarithmic,
+    /// Rises slowly, then quickly.
+    Exponential,
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Linear gain at `progress` through the fade, `0..=1`.
+    pub fn gain(&self, progress: f64) -> f64 {
+        let p = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => p,
+            FadeCurve::EqualPower => (p * std::f64::consts::FRAC_PI_2).sin(),
+            FadeCurve::Logarithmic => 1.0 - (1.0 - p) * (1.0 - p),
+            FadeCurve::Exponential => p * p,
+            FadeCurve::SCurve => p * p * (3.0 - 2.0 * p),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Fade {
+    pub duration: RationalTime,
+    #[serde(default)]
+    pub curve: FadeCurve,
+}
+
+impl Fade {
+    pub fn new(duration: RationalTime, curve: FadeCurve) -> Self {
+        Self { duration, curve }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioProperties {
+    /// Static clip gain in dB.
+    #[serde(default)]
+    pub gain_db: f64,
+    /// Keyframed level in dB on top of `gain_db`, with times as offsets
+    /// from the clip's start.
+    #[serde(default = "unity_envelope")]
+    pub envelope: AnimatedValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_in: Option<Fade>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fade_out: Option<Fade>,
+}
+
+fn unity_envelope() -> AnimatedValue {
+    AnimatedValue::new(0.0)
+}
+
+impl Default for AudioProperties {
+    fn default() -> Self {
+        Self {
+            gain_db: 0.0,
+            envelope: unity_envelope(),
+            fade_in: None,
+            fade_out: None,
+        }
+    }
+}
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// Silence maps to negative infinity.
+pub fn gain_to_db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+impl Clip {
+    pub fn with_gain_db(mut self, gain_db: f64) -> Self {
+        self.audio.gain_db = gain_db;
+        self
+    }
+
+    pub fn with_fades(mut self, fade_in: Option<Fade>, fade_out: Option<Fade>) -> Self {
+        self.audio.fade_in = fade_in;
+        self.audio.fade_out = fade_out;
+        self
+    }
+
+    /// The clip's own linear gain at timeline time `time`: gain, envelope
+    /// and fades, but nothing from its track. Silent outside the clip.
+    pub fn gain_at(&self, time: &RationalTime) -> f64 {
+        if !self.enabled || !self.timeline_range.contains(time) {
+            return 0.0;
+        }
+        let offset = *time - self.timeline_range.start;
+        let remaining = self.timeline_range.end() - *time;
+        let mut gain = db_to_gain(self.audio.gain_db + self.audio.envelope.at(&offset));
+        if let Some(fade) = &self.audio.fade_in {
+            gain *= fade.curve.gain(fraction(&offset, &fade.duration));
+        }
+        if let Some(fade) = &self.audio.fade_out {
+            gain *= fade.curve.gain(fraction(&remaining, &fade.duration));
+        }
+        gain
+    }
+}
+
+impl Track {
+    /// The track volume in dB; silence is negative infinity.
+    pub fn volume_db(&self) -> f64 {
+        gain_to_db(self.volume.max(0.0))
+    }
+
+    pub fn set_volume_db(&mut self, db: f64) {
+        self.volume = db_to_gain(db);
+    }
+
+    /// Constant-power left and right gains for the track's pan.
+    pub fn pan_gains(&self) -> (f64, f64) {
+        let angle = (self.pan.clamp(-1.0, 1.0) + 1.0) * std::f64::consts::FRAC_PI_4;
+        (angle.cos(), angle.sin())
+    }
+}
+
+impl Timeline {
+    /// Whether a track is heard: enabled, not muted, and for audio tracks
+    /// soloed whenever any audio track is soloed. Solo on other kinds of
+    /// track does not silence audio.
+    pub fn is_track_audible(&self, track: &Track) -> bool {
+        if !track.enabled || track.muted {
+            return false;
+        }
+        if track.kind != TrackKind::Audio {
+            return true;
+        }
+        track.solo || !self.tracks.iter().any(|t| t.kind == TrackKind::Audio && t.solo)
+    }
+
+    /// The linear gain a clip plays at `time`, combining its own gain,
+    /// envelope and fades with its track's volume, mute and solo. Pan is
+    /// left to the mixer through `Track::pan_gains`.
+    pub fn effective_gain(&self, track_id: Uuid, clip_id: Uuid, time: &RationalTime) -> Result<f64, TimelineError> {
+        let track = self.track(track_id).ok_or(TimelineError::TrackNotFound(track_id))?;
+        let clip = track.clip(clip_id).ok_or(TimelineError::ClipNotFound(clip_id))?;
+        if !self.is_track_audible(track) {
+            return Ok(0.0);
+        }
+        Ok(track.volume.max(0.0) * clip.gain_at(time))
+    }
+}
+
+/// How far `elapsed` is through `length`, or 1 once it has passed.
+fn fraction(elapsed: &RationalTime, length: &RationalTime) -> f64 {
+    if length.value <= 0 {
+        return 1.0;
+    }
+    elapsed.ratio_to(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Interpolation, Keyframe, TimeRange};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn clip() -> Clip {
+        let range = TimeRange::new(frames(0), frames(96));
+        Clip::new("music", "music.wav", range, range)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn gain_and_envelope_add_in_db() {
+        assert!(close(db_to_gain(-6.0), 0.501_187_233_627_272_2));
+        assert!(close(gain_to_db(db_to_gain(-12.5)), -12.5));
+        assert_eq!(gain_to_db(0.0), f64::NEG_INFINITY);
+
+        let mut clip = clip().with_gain_db(-6.0);
+        clip.audio.envelope.set_keyframe(Keyframe::new(frames(0), 0.0, Interpolation::Linear));
+        clip.audio.envelope.set_keyframe(Keyframe::new(frames(48), -6.0, Interpolation::Linear));
+        assert!(close(clip.gain_at(&frames(0)), db_to_gain(-6.0)));
+        assert!(close(clip.gain_at(&frames(24)), db_to_gain(-9.0)));
+        assert!(close(clip.gain_at(&frames(90)), db_to_gain(-12.0)));
+        assert_eq!(clip.gain_at(&frames(96)), 0.0);
+
+        clip.enabled = false;
+        assert_eq!(clip.gain_at(&frames(0)), 0.0);
+    }
+
+    #[test]
+    fn fades_shape_both_ends() {
+        let clip = clip().with_fades(
+            Some(Fade::new(frames(24), FadeCurve::Linear)),
+            Some(Fade::new(frames(24), FadeCurve::EqualPower)),
+        );
+        assert_eq!(clip.gain_at(&frames(0)), 0.0);
+        assert!(close(clip.gain_at(&frames(12)), 0.5));
+        assert!(close(clip.gain_at(&frames(48)), 1.0));
+        // 12 frames remain at frame 84.
+        assert!(close(clip.gain_at(&frames(84)), std::f64::consts::FRAC_1_SQRT_2));
+        assert!(clip.gain_at(&frames(95)) < 0.1);
+
+        for curve in [FadeCurve::Linear, FadeCurve::EqualPower, FadeCurve::Logarithmic, FadeCurve::Exponential, FadeCurve::SCurve] {
+            assert_eq!((curve.gain(0.0), curve.gain(1.0)), (0.0, 1.0));
+            assert!(curve.gain(0.25) < curve.gain(0.75));
+        }
+        assert!(FadeCurve::Logarithmic.gain(0.5) > 0.5 && FadeCurve::Exponential.gain(0.5) < 0.5);
+    }
+
+    #[test]
+    fn track_volume_reads_in_db() {
+        let mut track = Track::new("A1", TrackKind::Audio);
+        assert_eq!(track.volume_db(), 0.0);
+        track.set_volume_db(-6.0);
+        assert!(close(track.volume, db_to_gain(-6.0)));
+        assert!(close(track.volume_db(), -6.0));
+    }
+
+    /// A video track and two audio tracks, each holding `clip()`.
+    fn mix() -> (Timeline, [Uuid; 3], [Uuid; 3]) {
+        let mut timeline = Timeline::new("edit", 24);
+        let mut tracks = [
+            Track::new("V1", TrackKind::Video),
+            Track::new("A1", TrackKind::Audio),
+            Track::new("A2", TrackKind::Audio),
+        ];
+        let mut clips = [Uuid::nil(); 3];
+        for (track, id) in tracks.iter_mut().zip(clips.iter_mut()) {
+            let clip = clip();
+            *id = clip.id;
+            track.add_clip(clip).unwrap();
+        }
+        let ids = [tracks[0].id, tracks[1].id, tracks[2].id];
+        for track in tracks {
+            timeline.add_track(track);
+        }
+        (timeline, ids, clips)
+    }
+
+    fn gains(timeline: &Timeline, tracks: [Uuid; 3], clips: [Uuid; 3]) -> Vec<f64> {
+        tracks.iter().zip(clips).map(|(t, c)| timeline.effective_gain(*t, c, &frames(48)).unwrap()).collect()
+    }
+
+    #[test]
+    fn solo_and_mute() {
+        let (mut timeline, tracks, clips) = mix();
+        timeline.tracks[1].volume = 0.5;
+        assert_eq!(gains(&timeline, tracks, clips), vec![1.0, 0.5, 1.0]);
+
+        // Soloing picture leaves the sound alone.
+        timeline.tracks[0].solo = true;
+        assert_eq!(gains(&timeline, tracks, clips), vec![1.0, 0.5, 1.0]);
+
+        timeline.tracks[2].solo = true;
+        assert_eq!(gains(&timeline, tracks, clips), vec![1.0, 0.0, 1.0]);
+
+        timeline.tracks[2].muted = true;
+        assert_eq!(gains(&timeline, tracks, clips), vec![1.0, 0.0, 0.0]);
+
+        timeline.tracks[2].solo = false;
+        timeline.tracks[2].muted = false;
+        timeline.tracks[1].enabled = false;
+        assert_eq!(gains(&timeline, tracks, clips), vec![1.0, 0.0, 1.0]);
+
+        assert!(matches!(timeline.effective_gain(tracks[0], clips[1], &frames(0)), Err(TimelineError::ClipNotFound(_))));
+    }
+}
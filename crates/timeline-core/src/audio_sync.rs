@@ -0,0 +1,409 @@
+use std::f64::consts::TAU;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::link::{check_one_per_track, drift};
+use crate::{Clip, ClipLink, MediaSource, RationalTime, RoundingMode, TimeRange, Timeline, TimelineError};
+
+/// How two recordings of the same sound line up.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioSync {
+    /// The sample of the reference recording that plays together with the
+    /// other recording's first sample; negative when the other recording
+    /// started first.
+    pub offset_samples: i64,
+    pub sample_rate: u32,
+    /// Correlation of the overlapping audio at that offset, from 0 (no
+    /// likeness) to 1 (identical apart from level).
+    pub confidence: f64,
+}
+
+impl AudioSync {
+    /// Panics on a zero sample rate.
+    pub fn offset(&self) -> RationalTime {
+        RationalTime::new(self.offset_samples, self.sample_rate)
+    }
+}
+
+/// Finds where `other` lines up against `reference` by cross-correlating
+/// the two mono buffers. Both must share `sample_rate`. The search covers
+/// every offset at which they overlap in one FFT the size of both buffers
+/// together, so long recordings are best passed in downsampled. Returns
+/// `None` when either buffer is empty or silent.
+pub fn find_offset(reference: &[f32], other: &[f32], sample_rate: u32) -> Option<AudioSync> {
+    if reference.is_empty() || other.is_empty() {
+        return None;
+    }
+    let a = centred(reference);
+    let b = centred(other);
+    let size = (a.len() + b.len() - 1).next_power_of_two();
+
+    let mut spectrum = to_complex(&a, size);
+    let mut other_spectrum = to_complex(&b, size);
+    fft(&mut spectrum, false);
+    fft(&mut other_spectrum, false);
+    for (x, y) in spectrum.iter_mut().zip(&other_spectrum) {
+        *x = *x * y.conj();
+    }
+    fft(&mut spectrum, true);
+
+    // Lag `k` holds the sum of `a[t + k] * b[t]`; negative lags wrap round
+    // to the end of the buffer.
+    let (lag, peak) = (-(b.len() as i64 - 1)..a.len() as i64)
+        .map(|lag| (lag, spectrum[lag.rem_euclid(size as i64) as usize].re))
+        .max_by(|x, y| x.1.total_cmp(&y.1))?;
+
+    let first = (-lag).max(0) as usize;
+    let last = (a.len() as i64 - lag).min(b.len() as i64) as usize;
+    let energy = |samples: &[f64]| samples.iter().map(|s| s * s).sum::<f64>();
+    let energy_a = energy(&a[(first as i64 + lag) as usize..(last as i64 + lag) as usize]);
+    let energy_b = energy(&b[first..last]);
+    let scale = (energy_a * energy_b).sqrt();
+    if scale <= 0.0 {
+        return None;
+    }
+    Some(AudioSync {
+        offset_samples: lag,
+        sample_rate,
+        confidence: (peak / scale).clamp(0.0, 1.0),
+    })
+}
+
+impl Timeline {
+    /// Places `recording` on `track_id` under the clip `reference_id`,
+    /// lined up by `sync` as found between the clip's media and the
+    /// recording, and links the two. The new clip covers as much of the
+    /// reference clip as the recording does, rounded inwards to whole
+    /// timeline frames. Returns the new clip's id.
+    pub fn add_synced_clip(
+        &mut self,
+        reference_id: Uuid,
+        recording: MediaSource,
+        sync: &AudioSync,
+        track_id: Uuid,
+    ) -> Result<Uuid, TimelineError> {
+        let (_, reference) = self.find_clip(reference_id).ok_or(TimelineError::ClipNotFound(reference_id))?;
+        if reference.is_retimed() {
+            return Err(TimelineError::InvalidState(format!(
+                "clip {} is retimed and cannot be synced",
+                reference_id
+            )));
+        }
+
+        // Where the recording's first sample plays on the timeline.
+        let start = reference.sync_point().checked_add(&RationalTime::try_new(sync.offset_samples, sync.sample_rate)?)?;
+        let available = match recording.available_range() {
+            Some(range) => range.shifted_by(start),
+            None => TimeRange::from_start_end(start, reference.timeline_range.end().max(start)),
+        };
+        let rate = self.metadata.frame_rate;
+        let covered = reference.timeline_range.intersection(&available);
+        let Some((first, last)) = covered
+            .map(|range| (range.start.rescaled_with(rate, RoundingMode::Ceil), range.end().rescaled_with(rate, RoundingMode::Floor)))
+            .filter(|(first, last)| first < last)
+        else {
+            return Err(TimelineError::InvalidState(format!(
+                "recording {} does not overlap clip {}",
+                recording.path, reference_id
+            )));
+        };
+
+        let name = Path::new(&recording.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| recording.path.clone());
+        let source_start = (first - start).rescaled(sync.sample_rate);
+        let duration = last - first;
+        let mut clip = Clip::new(name, recording, TimeRange::new(source_start, duration), TimeRange::new(first, duration));
+        let clip_id = clip.id;
+
+        match reference.link {
+            // Join the existing group, carrying over any drift it already has.
+            Some(link) => {
+                let mut track_ids: Vec<Uuid> = self.linked_clips(link.group_id).into_iter().map(|(track_id, _)| track_id).collect();
+                track_ids.push(track_id);
+                check_one_per_track(&track_ids)?;
+                let anchor = clip.sync_point() - drift(reference);
+                clip.link = Some(ClipLink { group_id: link.group_id, anchor });
+                self.add_clip_to_track(track_id, clip)?;
+            }
+            None => {
+                self.add_clip_to_track(track_id, clip)?;
+                if let Err(err) = self.link_clips(&[reference_id, clip_id]) {
+                    self.remove_clip(track_id, clip_id)?;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(clip_id)
+    }
+}
+
+fn centred(samples: &[f32]) -> Vec<f64> {
+    let mean = samples.iter().map(|&s| s as f64).sum::<f64>() / samples.len() as f64;
+    samples.iter().map(|&s| s as f64 - mean).collect()
+}
+
+fn to_complex(samples: &[f64], size: usize) -> Vec<Complex> {
+    let mut buffer = vec![Complex::default(); size];
+    for (slot, &sample) in buffer.iter_mut().zip(samples) {
+        slot.re = sample;
+    }
+    buffer
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn from_angle(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { re: cos, im: sin }
+    }
+
+    fn conj(self) -> Self {
+        Self { re: self.re, im: -self.im }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex { re: self.re + other.re, im: self.im + other.im }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex { re: self.re - other.re, im: self.im - other.im }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/// In-place iterative radix-2 FFT. `buffer.len()` must be a power of two.
+/// The inverse transform is scaled by `1 / len`.
+fn fft(buffer: &mut [Complex], inverse: bool) {
+    let size = buffer.len();
+    let mut j = 0;
+    for i in 1..size {
+        let mut bit = size >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= size {
+        let twiddles: Vec<Complex> = (0..len / 2)
+            .map(|k| Complex::from_angle(sign * TAU * k as f64 / len as f64))
+            .collect();
+        for chunk in buffer.chunks_mut(len) {
+            let (low, high) = chunk.split_at_mut(len / 2);
+            for ((a, b), w) in low.iter_mut().zip(high.iter_mut()).zip(&twiddles) {
+                let t = *b * *w;
+                *b = *a - t;
+                *a = *a + t;
+            }
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / size as f64;
+        for value in buffer.iter_mut() {
+            value.re *= scale;
+            value.im *= scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MediaMetadata, Track, TrackKind};
+
+    const RATE: u32 = 48000;
+
+    fn noise(seed: u64, len: usize) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                ((state >> 40) as f32 / (1u64 << 24) as f32) * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn seconds(value: i64) -> RationalTime {
+        RationalTime::new(value, 1)
+    }
+
+    fn media(path: &str, duration: RationalTime) -> MediaSource {
+        MediaSource::new(path).with_metadata(MediaMetadata { duration, ..MediaMetadata::default() })
+    }
+
+    /// A timeline with a ten second camera clip at 5s on a video track and
+    /// an empty audio track.
+    fn timeline() -> (Timeline, Uuid, Uuid, Uuid) {
+        let mut timeline = Timeline::new("sync", 24);
+        let video = Track::new("V1", TrackKind::Video);
+        let audio = Track::new("A1", TrackKind::Audio);
+        let (video_id, audio_id) = (video.id, audio.id);
+        timeline.add_track(video);
+        timeline.add_track(audio);
+        let camera = Clip::new(
+            "camera",
+            media("camera.mov", seconds(60)),
+            TimeRange::new(seconds(0), seconds(10)),
+            TimeRange::new(seconds(5), seconds(10)),
+        );
+        let camera_id = camera.id;
+        timeline.add_clip_to_track(video_id, camera).unwrap();
+        (timeline, video_id, audio_id, camera_id)
+    }
+
+    fn sync(offset_samples: i64) -> AudioSync {
+        AudioSync { offset_samples, sample_rate: RATE, confidence: 1.0 }
+    }
+
+    #[test]
+    fn finds_a_late_recording() {
+        let reference = noise(1, 8192);
+        let other = reference[1234..1234 + 4000].to_vec();
+        let found = find_offset(&reference, &other, RATE).unwrap();
+        assert_eq!(found.offset_samples, 1234);
+        assert!(found.confidence > 0.99, "confidence {}", found.confidence);
+        assert_eq!(found.offset(), RationalTime::new(1234, RATE));
+    }
+
+    #[test]
+    fn finds_an_early_recording() {
+        let reference = noise(2, 8192);
+        let mut other = noise(3, 300);
+        other.extend_from_slice(&reference[..4000]);
+        let found = find_offset(&reference, &other, RATE).unwrap();
+        assert_eq!(found.offset_samples, -300);
+        assert!(found.confidence > 0.99, "confidence {}", found.confidence);
+    }
+
+    #[test]
+    fn unrelated_audio_has_low_confidence() {
+        let found = find_offset(&noise(4, 4096), &noise(5, 4096), RATE).unwrap();
+        assert!(found.confidence < 0.2, "confidence {}", found.confidence);
+    }
+
+    #[test]
+    fn empty_or_silent_input_finds_nothing() {
+        let sound = noise(6, 1024);
+        assert_eq!(find_offset(&[], &sound, RATE), None);
+        assert_eq!(find_offset(&sound, &[], RATE), None);
+        assert_eq!(find_offset(&sound, &[0.0; 512], RATE), None);
+        // A constant level is silence once the mean is taken out.
+        assert_eq!(find_offset(&[0.5; 512], &sound, RATE), None);
+    }
+
+    #[test]
+    fn synced_clip_links_to_an_unlinked_reference() {
+        let (mut timeline, _, audio_id, camera_id) = timeline();
+        // The recorder started two seconds into the camera file and ran
+        // for twenty seconds, so it covers the clip from 7s to its end.
+        let recording = media("zoom/take1.wav", seconds(20));
+        let clip_id = timeline.add_synced_clip(camera_id, recording, &sync(2 * RATE as i64), audio_id).unwrap();
+
+        let clip = timeline.track(audio_id).unwrap().clip(clip_id).unwrap();
+        assert_eq!(clip.name, "take1");
+        assert_eq!(clip.timeline_range, TimeRange::new(seconds(7), seconds(8)));
+        assert_eq!(clip.source_range.start, seconds(0));
+        let group_id = clip.link.unwrap().group_id;
+        let members: Vec<Uuid> = timeline.linked_clips(group_id).iter().map(|(_, c)| c.id).collect();
+        assert_eq!(members, vec![camera_id, clip_id]);
+        assert!(timeline.sync_offsets().is_empty());
+    }
+
+    #[test]
+    fn synced_clip_joins_an_existing_group_and_keeps_its_drift() {
+        let (mut timeline, video_id, audio_id, camera_id) = timeline();
+        let mut scratch = Track::new("A2", TrackKind::Audio);
+        let scratch_clip = Clip::new(
+            "scratch",
+            media("camera.mov", seconds(60)),
+            TimeRange::new(seconds(0), seconds(10)),
+            TimeRange::new(seconds(5), seconds(10)),
+        );
+        let scratch_id = scratch_clip.id;
+        scratch.add_clip(scratch_clip).unwrap();
+        timeline.add_track(scratch);
+        let group_id = timeline.link_clips(&[camera_id, scratch_id]).unwrap();
+
+        // Knock the camera a frame out of sync with its scratch audio.
+        let rate = timeline.metadata.frame_rate;
+        let camera = timeline.track_mut(video_id).unwrap().clips.iter_mut().find(|c| c.id == camera_id).unwrap();
+        camera.source_range.start = camera.source_range.start + RationalTime::new(1, rate);
+
+        let recording = media("zoom/take1.wav", seconds(20));
+        let clip_id = timeline.add_synced_clip(camera_id, recording, &sync(0), audio_id).unwrap();
+
+        let clip = timeline.track(audio_id).unwrap().clip(clip_id).unwrap();
+        assert_eq!(clip.link.unwrap().group_id, group_id);
+        assert_eq!(timeline.linked_clips(group_id).len(), 3);
+        // The new clip drifts with the camera, so only the scratch audio
+        // is reported out of sync.
+        let offsets = timeline.sync_offsets();
+        assert_eq!(offsets.len(), 1);
+        assert_eq!(offsets[0].clip_id, scratch_id);
+        assert_eq!(offsets[0].frames, 1);
+    }
+
+    #[test]
+    fn recording_that_misses_the_clip_is_rejected() {
+        let (mut timeline, _, audio_id, camera_id) = timeline();
+        let recording = media("zoom/take1.wav", seconds(5));
+        let result = timeline.add_synced_clip(camera_id, recording, &sync(30 * RATE as i64), audio_id);
+        assert!(matches!(result, Err(TimelineError::InvalidState(_))));
+        assert!(timeline.track(audio_id).unwrap().clips.is_empty());
+    }
+
+    #[test]
+    fn synced_clip_cannot_join_a_group_twice_on_one_track() {
+        let (mut timeline, _, audio_id, camera_id) = timeline();
+        // The camera's own sound sits later on A1, clear of where the
+        // recording would go.
+        let sound = Clip::new(
+            "camera",
+            media("camera.mov", seconds(60)),
+            TimeRange::new(seconds(0), seconds(10)),
+            TimeRange::new(seconds(20), seconds(10)),
+        );
+        let sound_id = sound.id;
+        timeline.add_clip_to_track(audio_id, sound).unwrap();
+        let group_id = timeline.link_clips(&[camera_id, sound_id]).unwrap();
+
+        let result = timeline.add_synced_clip(camera_id, media("zoom/take1.wav", seconds(20)), &sync(0), audio_id);
+        assert!(matches!(result, Err(TimelineError::InvalidState(_))));
+        assert_eq!(timeline.track(audio_id).unwrap().clips.len(), 1);
+        assert_eq!(timeline.linked_clips(group_id).len(), 2);
+    }
+}
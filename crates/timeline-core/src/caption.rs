@@ -0,0 +1,197 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+use crate::{Color, Point, RationalTime, TimeRange, Timeline, TimelineError, Track, TrackKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// Styling for one caption. Unset fields fall back to the player's or
+/// renderer's defaults.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CaptionStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    /// In timeline pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<Color>,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub italic: bool,
+    #[serde(default)]
+    pub align: TextAlign,
+}
+
+/// A subtitle cue shown over `range` of the timeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Caption {
+    pub id: Uuid,
+    pub text: String,
+    pub range: TimeRange,
+    #[serde(default)]
+    pub style: CaptionStyle,
+    /// Anchor of the text block in normalized frame coordinates.
+    #[serde(default = "default_position")]
+    pub position: Point,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+}
+
+fn default_position() -> Point {
+    Point::new(0.5, 0.9)
+}
+
+impl Caption {
+    pub fn new(text: impl Into<String>, range: TimeRange) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            text: text.into(),
+            range,
+            style: CaptionStyle::default(),
+            position: default_position(),
+            speaker: None,
+        }
+    }
+
+    pub fn with_style(mut self, style: CaptionStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_position(mut self, position: Point) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_speaker(mut self, speaker: impl Into<String>) -> Self {
+        self.speaker = Some(speaker.into());
+        self
+    }
+}
+
+impl Track {
+    pub fn captions(&self) -> &[Caption] {
+        &self.captions
+    }
+
+    /// Inserts a caption in time order. Only subtitle tracks hold captions,
+    /// and captions on one track may not overlap.
+    pub fn add_caption(&mut self, caption: Caption) -> Result<(), TimelineError> {
+        if self.kind != TrackKind::Subtitle {
+            return Err(TimelineError::InvalidState(format!(
+                "track {} is not a subtitle track",
+                self.id
+            )));
+        }
+        if self.locked {
+            return Err(TimelineError::TrackLocked(self.id));
+        }
+        if caption.range.is_empty() {
+            return Err(TimelineError::InvalidTimeRange {
+                start: caption.range.start.value,
+                end: caption.range.end().rescaled(caption.range.start.rate).value,
+            });
+        }
+        let index = self.captions.partition_point(|c| c.range.start < caption.range.start);
+        let neighbours = [index.checked_sub(1), Some(index)];
+        for existing in neighbours.into_iter().flatten().filter_map(|i| self.captions.get(i)) {
+            if existing.range.overlaps(&caption.range) {
+                return Err(overlap_error(existing, &caption));
+            }
+        }
+        self.captions.insert(index, caption);
+        Ok(())
+    }
+
+    pub fn remove_caption(&mut self, caption_id: Uuid) -> Option<Caption> {
+        let pos = self.captions.iter().position(|c| c.id == caption_id)?;
+        Some(self.captions.remove(pos))
+    }
+
+    pub fn caption_at_time(&self, time: &RationalTime) -> Option<&Caption> {
+        let index = self.captions.partition_point(|c| c.range.start <= *time);
+        let caption = self.captions.get(index.checked_sub(1)?)?;
+        caption.range.contains(time).then_some(caption)
+    }
+
+    /// Every caption that overlaps `range`, in time order.
+    pub fn captions_in_range(&self, range: &TimeRange) -> &[Caption] {
+        let first = self.captions.partition_point(|c| c.range.end() <= range.start);
+        let last = self.captions.partition_point(|c| c.range.start < range.end());
+        &self.captions[first..last.max(first)]
+    }
+
+    /// Pushes every caption from the start of `range` onwards later by its
+    /// duration, the way [`Track::insert_gap`] moves clips. A caption
+    /// straddling the start is split in two.
+    pub(crate) fn open_caption_gap(&mut self, range: TimeRange) {
+        self.split_captions_at(range.start);
+        for caption in self.captions.iter_mut().filter(|c| c.range.start >= range.start) {
+            caption.range = caption.range.shifted_by(range.duration);
+        }
+    }
+
+    /// Removes the part of every caption inside `range`, and with `ripple`
+    /// pulls later captions back to close the gap, the way
+    /// [`Track::lift_range`] and [`Track::extract_range`] treat clips.
+    pub(crate) fn cut_captions(&mut self, range: TimeRange, ripple: bool) {
+        self.split_captions_at(range.start);
+        self.split_captions_at(range.end());
+        self.captions.retain(|c| !range.contains_range(&c.range));
+        if ripple {
+            for caption in self.captions.iter_mut().filter(|c| c.range.start >= range.end()) {
+                caption.range = caption.range.shifted_by(-range.duration);
+            }
+        }
+    }
+
+    fn split_captions_at(&mut self, at: RationalTime) {
+        let split = self.captions.iter().enumerate().find_map(|(i, c)| Some((i, c.range.split_at(&at)?)));
+        let Some((index, (head, tail))) = split else {
+            return;
+        };
+        self.captions[index].range = head;
+        let tail = Caption { id: Uuid::new_v4(), range: tail, ..self.captions[index].clone() };
+        self.captions.insert(index + 1, tail);
+    }
+}
+
+impl Timeline {
+    pub fn subtitle_tracks(&self) -> impl Iterator<Item = &Track> {
+        self.tracks.iter().filter(|t| t.kind == TrackKind::Subtitle)
+    }
+
+    /// The caption showing at `time` on the first enabled subtitle track
+    /// that has one.
+    pub fn caption_at_time(&self, time: &RationalTime) -> Option<&Caption> {
+        self.subtitle_tracks()
+            .filter(|t| t.enabled)
+            .find_map(|track| track.caption_at_time(time))
+    }
+}
+
+fn overlap_error(existing: &Caption, caption: &Caption) -> TimelineError {
+    let time = existing.range.start.max(caption.range.start);
+    TimelineError::InvalidState(format!("captions overlap at {}s", time.to_seconds()))
+}
+
+pub(crate) fn deserialize_captions<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Caption>, D::Error> {
+    let mut captions = Vec::<Caption>::deserialize(deserializer)?;
+    captions.sort_by_key(|c| c.range.start);
+    for pair in captions.windows(2) {
+        if pair[0].range.overlaps(&pair[1].range) {
+            return Err(serde::de::Error::custom(overlap_error(&pair[0], &pair[1])));
+        }
+    }
+    Ok(captions)
+}
@@ -0,0 +1,323 @@
+use std::fmt::Write;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{MarkerKind, RationalTime, Timeline, TimelineError};
+
+/// Shortest gap between chapter starts that players such as YouTube accept.
+const MIN_SPACING_SECONDS: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChapterFormat {
+    /// `;FFMETADATA1` file for `ffmpeg -i in -i chapters.txt -map_metadata 1`.
+    FfMetadata,
+    /// Matroska XML chapters for `mkvmerge --chapters`.
+    MatroskaXml,
+    WebVtt,
+    /// `00:00 Title` lines for a YouTube description.
+    YouTube,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub id: Uuid,
+    pub title: String,
+    pub start: RationalTime,
+    pub end: RationalTime,
+}
+
+impl Timeline {
+    /// Chapter markers at any level, each running until the next one or,
+    /// for the last, until the end of the timeline. A marker with a
+    /// duration ends earlier if it says so.
+    pub fn chapters(&self) -> Vec<Chapter> {
+        let mut markers: Vec<_> = self.all_markers().filter(|m| m.kind == MarkerKind::Chapter).collect();
+        markers.sort_by_key(|m| m.time);
+
+        let duration = self.duration();
+        let starts: Vec<_> = markers.iter().map(|m| m.time).skip(1).collect();
+        markers
+            .iter()
+            .enumerate()
+            .map(|(i, marker)| {
+                let next = starts.get(i).copied().unwrap_or(duration).max(marker.time);
+                let end = match marker.duration {
+                    Some(length) => (marker.time + length).min(next),
+                    None => next,
+                };
+                Chapter {
+                    id: marker.id,
+                    title: marker.name.clone(),
+                    start: marker.time,
+                    end,
+                }
+            })
+            .collect()
+    }
+
+    pub fn export_chapters(&self, format: ChapterFormat) -> Result<String, TimelineError> {
+        export_chapters(&self.chapters(), format)
+    }
+}
+
+/// Checks the rules shared by the delivery formats: the first chapter
+/// starts at zero and starts are at least ten seconds apart.
+pub fn validate_chapters(chapters: &[Chapter]) -> Result<(), TimelineError> {
+    let first = chapters
+        .first()
+        .ok_or_else(|| TimelineError::InvalidChapters("there are no chapter markers".to_string()))?;
+    if !first.start.is_zero() {
+        return Err(TimelineError::InvalidChapters(format!(
+            "first chapter \"{}\" starts at {}s instead of 0",
+            first.title,
+            first.start.to_seconds()
+        )));
+    }
+    let min_spacing = RationalTime::new(MIN_SPACING_SECONDS, 1);
+    for pair in chapters.windows(2) {
+        if pair[1].start - pair[0].start < min_spacing {
+            return Err(TimelineError::InvalidChapters(format!(
+                "chapter \"{}\" starts less than {}s after \"{}\"",
+                pair[1].title, MIN_SPACING_SECONDS, pair[0].title
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub fn export_chapters(chapters: &[Chapter], format: ChapterFormat) -> Result<String, TimelineError> {
+    validate_chapters(chapters)?;
+    Ok(match format {
+        ChapterFormat::FfMetadata => ffmetadata(chapters),
+        ChapterFormat::MatroskaXml => matroska_xml(chapters),
+        ChapterFormat::WebVtt => webvtt(chapters),
+        ChapterFormat::YouTube => youtube(chapters),
+    })
+}
+
+fn ffmetadata(chapters: &[Chapter]) -> String {
+    let mut out = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        // Frame counts against the timeline's own time base stay exact
+        // for NTSC rates.
+        let rate = chapter.start.rate;
+        let _ = write!(
+            out,
+            "\n[CHAPTER]\nTIMEBASE={}/{}\nSTART={}\nEND={}\ntitle={}\n",
+            rate.denominator,
+            rate.numerator,
+            chapter.start.value,
+            chapter.end.rescaled(rate).value,
+            escape_ffmetadata(&chapter.title)
+        );
+    }
+    out
+}
+
+fn matroska_xml(chapters: &[Chapter]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n\
+         <Chapters>\n  <EditionEntry>\n",
+    );
+    for chapter in chapters {
+        let _ = write!(
+            out,
+            "    <ChapterAtom>\n\
+             \x20     <ChapterUID>{}</ChapterUID>\n\
+             \x20     <ChapterTimeStart>{}</ChapterTimeStart>\n\
+             \x20     <ChapterTimeEnd>{}</ChapterTimeEnd>\n\
+             \x20     <ChapterDisplay>\n\
+             \x20       <ChapterString>{}</ChapterString>\n\
+             \x20       <ChapterLanguage>und</ChapterLanguage>\n\
+             \x20     </ChapterDisplay>\n\
+             \x20   </ChapterAtom>\n",
+            chapter.id.as_u64_pair().0.max(1),
+            clock(&chapter.start, 9),
+            clock(&chapter.end, 9),
+            escape_xml(&chapter.title)
+        );
+    }
+    out.push_str("  </EditionEntry>\n</Chapters>\n");
+    out
+}
+
+fn webvtt(chapters: &[Chapter]) -> String {
+    let mut out = String::from("WEBVTT\n");
+    for (index, chapter) in chapters.iter().enumerate() {
+        let _ = write!(
+            out,
+            "\n{}\n{} --> {}\n{}\n",
+            index + 1,
+            clock(&chapter.start, 3),
+            clock(&chapter.end, 3),
+            escape_markup(&chapter.title).replace('\n', " ")
+        );
+    }
+    out
+}
+
+fn youtube(chapters: &[Chapter]) -> String {
+    let with_hours = chapters.last().is_some_and(|c| c.start.to_seconds() >= 3600.0);
+    chapters
+        .iter()
+        .map(|chapter| {
+            let seconds = chapter.start.to_seconds().floor() as i64;
+            let stamp = if with_hours {
+                format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+            } else {
+                format!("{:02}:{:02}", seconds / 60, seconds % 60)
+            };
+            format!("{} {}\n", stamp, chapter.title.replace('\n', " "))
+        })
+        .collect()
+}
+
+/// `HH:MM:SS.fff` with `digits` of sub-second precision, rounded to the
+/// nearest unit.
+fn clock(time: &RationalTime, digits: u32) -> String {
+    let scale = 10i128.pow(digits);
+    let units = (time.value as i128 * time.rate.denominator as i128 * scale * 2 / time.rate.numerator as i128 + 1) / 2;
+    let seconds = units / scale;
+    format!(
+        "{:02}:{:02}:{:02}.{:0width$}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        units % scale,
+        width = digits as usize
+    )
+}
+
+fn escape_ffmetadata(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_xml(text: &str) -> String {
+    escape_markup(text).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameRate;
+
+    fn chapter(uid: u64, title: &str, start: i64, end: i64) -> Chapter {
+        Chapter {
+            id: Uuid::from_u64_pair(uid, 0),
+            title: title.to_string(),
+            start: RationalTime::new(start, FrameRate::FPS_29_97),
+            end: RationalTime::new(end, FrameRate::FPS_29_97),
+        }
+    }
+
+    /// Frames 0, 450 (15.015s) and 2000 (66.733s) at 29.97.
+    fn chapters() -> Vec<Chapter> {
+        vec![chapter(1, "Intro", 0, 450), chapter(2, "Q&A; <live>", 450, 2000)]
+    }
+
+    #[test]
+    fn ffmetadata_golden() {
+        let expected = "\
+;FFMETADATA1
+
+[CHAPTER]
+TIMEBASE=1001/30000
+START=0
+END=450
+title=Intro
+
+[CHAPTER]
+TIMEBASE=1001/30000
+START=450
+END=2000
+title=Q&A\\; <live>
+";
+        assert_eq!(export_chapters(&chapters(), ChapterFormat::FfMetadata).unwrap(), expected);
+    }
+
+    #[test]
+    fn matroska_xml_golden() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE Chapters SYSTEM "matroskachapters.dtd">
+<Chapters>
+  <EditionEntry>
+    <ChapterAtom>
+      <ChapterUID>1</ChapterUID>
+      <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+      <ChapterTimeEnd>00:00:15.015000000</ChapterTimeEnd>
+      <ChapterDisplay>
+        <ChapterString>Intro</ChapterString>
+        <ChapterLanguage>und</ChapterLanguage>
+      </ChapterDisplay>
+    </ChapterAtom>
+    <ChapterAtom>
+      <ChapterUID>2</ChapterUID>
+      <ChapterTimeStart>00:00:15.015000000</ChapterTimeStart>
+      <ChapterTimeEnd>00:01:06.733333333</ChapterTimeEnd>
+      <ChapterDisplay>
+        <ChapterString>Q&amp;A; &lt;live&gt;</ChapterString>
+        <ChapterLanguage>und</ChapterLanguage>
+      </ChapterDisplay>
+    </ChapterAtom>
+  </EditionEntry>
+</Chapters>
+"#;
+        assert_eq!(export_chapters(&chapters(), ChapterFormat::MatroskaXml).unwrap(), expected);
+    }
+
+    #[test]
+    fn webvtt_golden() {
+        let expected = "\
+WEBVTT
+
+1
+00:00:00.000 --> 00:00:15.015
+Intro
+
+2
+00:00:15.015 --> 00:01:06.733
+Q&amp;A; &lt;live&gt;
+";
+        assert_eq!(export_chapters(&chapters(), ChapterFormat::WebVtt).unwrap(), expected);
+    }
+
+    #[test]
+    fn youtube_golden() {
+        assert_eq!(export_chapters(&chapters(), ChapterFormat::YouTube).unwrap(), "00:00 Intro\n00:15 Q&A; <live>\n");
+
+        // Hours appear on every line once any chapter needs them.
+        let mut long = chapters();
+        long.push(chapter(3, "Outro", 108_000, 108_500));
+        assert_eq!(
+            export_chapters(&long, ChapterFormat::YouTube).unwrap(),
+            "0:00:00 Intro\n0:00:15 Q&A; <live>\n1:00:03 Outro\n"
+        );
+    }
+
+    #[test]
+    fn first_chapter_must_start_at_zero() {
+        assert!(matches!(validate_chapters(&[]), Err(TimelineError::InvalidChapters(_))));
+        let late = [chapter(1, "Intro", 1, 450)];
+        assert!(matches!(validate_chapters(&late), Err(TimelineError::InvalidChapters(_))));
+    }
+
+    #[test]
+    fn chapters_must_be_ten_seconds_apart() {
+        // 300 frames at 29.97 is 10.01s, 299 is 9.977s.
+        assert!(validate_chapters(&[chapter(1, "a", 0, 300), chapter(2, "b", 300, 600)]).is_ok());
+        let close = [chapter(1, "a", 0, 299), chapter(2, "b", 299, 600)];
+        assert!(matches!(validate_chapters(&close), Err(TimelineError::InvalidChapters(_))));
+        assert!(export_chapters(&close, ChapterFormat::WebVtt).is_err());
+    }
+}
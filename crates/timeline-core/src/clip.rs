@@ -1,30 +1,197 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::{MediaSource, TimeRange};
+use crate::audio::gain_to_db;
+use crate::effect::deserialize_effects;
+use crate::{AnimatedValue, AudioProperties, ClipLink, ClipProperties, ClipSource, Effect, Marker, ParameterValue, RationalTime, TimeRange, TimelineError, TrimEdge};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ClipRepr")]
 pub struct Clip {
     pub id: Uuid,
     pub name: String,
-    pub source: MediaSource,
+    pub source: ClipSource,
     pub source_range: TimeRange,
     pub timeline_range: TimeRange,
-    pub effects: Vec<String>,
-    pub markers: Vec<String>,
+    pub effects: Vec<Effect>,
+    /// Placed in source time so they travel with the media through moves,
+    /// trims and splits. Ordered by time.
+    pub markers: Vec<Marker>,
+    pub properties: ClipProperties,
+    pub audio: AudioProperties,
+    /// Constant playback speed; 2.0 plays twice as fast.
+    pub speed: f64,
+    /// Plays the source range backwards at `speed`.
+    pub reversed: bool,
+    /// A single source frame held for the whole clip.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeze_frame: Option<RationalTime>,
+    /// Source time in seconds from the start of the media against the
+    /// offset into the clip, for speed ramps. Replaces `speed` and
+    /// `reversed`; `source_range` follows the media the curve reaches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_remap: Option<AnimatedValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<ClipLink>,
     pub enabled: bool,
     pub locked: bool,
 }
 
+/// A clip as saved, including the layouts older versions wrote. Markers
+/// saved as bare names are placed at the start of the clip's source range.
+#[derive(Deserialize)]
+struct ClipRepr {
+    id: Uuid,
+    name: String,
+    source: ClipSource,
+    source_range: TimeRange,
+    timeline_range: TimeRange,
+    #[serde(default, deserialize_with = "deserialize_effects")]
+    effects: Vec<Effect>,
+    #[serde(default)]
+    markers: Vec<MarkerRepr>,
+    #[serde(default)]
+    properties: PropertiesRepr,
+    #[serde(default)]
+    audio: Option<AudioProperties>,
+    #[serde(default)]
+    speed: Option<f64>,
+    #[serde(default)]
+    reversed: Option<bool>,
+    #[serde(default)]
+    freeze_frame: Option<RationalTime>,
+    #[serde(default)]
+    time_remap: Option<AnimatedValue>,
+    #[serde(default)]
+    link: Option<ClipLink>,
+    enabled: bool,
+    locked: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MarkerRepr {
+    Name(String),
+    Marker(Marker),
+}
+
+/// `Clip.properties` as the TS schema writes it, where retiming and the
+/// linear clip volume sit beside the picture properties. The clip's own
+/// `speed`, `reversed` and `audio` win when both are present.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PropertiesRepr {
+    #[serde(flatten)]
+    properties: ClipProperties,
+    #[serde(default)]
+    speed: Option<f64>,
+    #[serde(default)]
+    is_reversed: Option<bool>,
+    #[serde(default)]
+    volume: Option<f64>,
+}
+
+/// Quietest gain a linear TS volume maps to, so a volume of zero stays a
+/// finite number of dB.
+const SILENCE_DB: f64 = -96.0;
+
+impl TryFrom<ClipRepr> for Clip {
+    type Error = String;
+
+    fn try_from(repr: ClipRepr) -> Result<Self, Self::Error> {
+        let speed = repr.speed.or(repr.properties.speed).unwrap_or_else(default_speed);
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(format!("speed must be positive, got {}", speed));
+        }
+        let audio = match (repr.audio, repr.properties.volume) {
+            (Some(audio), _) => audio,
+            (None, Some(volume)) if !volume.is_finite() || volume < 0.0 => {
+                return Err(format!("volume must be a non-negative gain, got {}", volume));
+            }
+            (None, Some(volume)) => AudioProperties { gain_db: gain_to_db(volume).max(SILENCE_DB), ..AudioProperties::default() },
+            (None, None) => AudioProperties::default(),
+        };
+        let start = repr.source_range.start;
+        let mut markers: Vec<Marker> = repr
+            .markers
+            .into_iter()
+            .map(|marker| match marker {
+                MarkerRepr::Name(name) => Marker::new(name, start),
+                MarkerRepr::Marker(marker) => marker,
+            })
+            .collect();
+        markers.sort_by_key(|m| m.time);
+        Ok(Self {
+            id: repr.id,
+            name: repr.name,
+            source: repr.source,
+            source_range: repr.source_range,
+            timeline_range: repr.timeline_range,
+            effects: repr.effects,
+            markers,
+            properties: repr.properties.properties,
+            audio,
+            speed,
+            reversed: repr.reversed.or(repr.properties.is_reversed).unwrap_or(false),
+            freeze_frame: repr.freeze_frame,
+            time_remap: repr.time_remap,
+            link: repr.link,
+            enabled: repr.enabled,
+            locked: repr.locked,
+        })
+    }
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+/// `speed` as the simplest fraction that rounds to it, so speeds such as
+/// 1/3 or 1000/1001 retime by their exact ratio rather than through
+/// seconds in `f64`.
+fn speed_ratio(speed: f64) -> (i64, i64) {
+    const MAX_DENOMINATOR: i64 = 100_000;
+    // Continued fraction convergents `h / k` of `speed`.
+    let (mut h, mut previous_h) = (1i64, 0i64);
+    let (mut k, mut previous_k) = (0i64, 1i64);
+    let mut rest = speed;
+    loop {
+        let whole = rest.floor();
+        let (next_h, next_k) = (whole as i64 * h + previous_h, whole as i64 * k + previous_k);
+        if next_k > MAX_DENOMINATOR {
+            break;
+        }
+        (previous_h, h, previous_k, k) = (h, next_h, k, next_k);
+        let fraction = rest - whole;
+        if fraction < 1e-9 || (h as f64 / k as f64 - speed).abs() <= speed * 1e-12 {
+            break;
+        }
+        rest = 1.0 / fraction;
+    }
+    (h, k)
+}
+
 impl Clip {
-    pub fn new(name: impl Into<String>, source: MediaSource) -> Self {
+    pub fn new(
+        name: impl Into<String>,
+        source: impl Into<ClipSource>,
+        source_range: TimeRange,
+        timeline_range: TimeRange,
+    ) -> Self {
         Self {
             id: Uuid::new_v4(),
             name: name.into(),
-            source,
-            source_range: TimeRange::default(),
-            timeline_range: TimeRange::default(),
+            source: source.into(),
+            source_range,
+            timeline_range,
             effects: Vec::new(),
             markers: Vec::new(),
+            properties: ClipProperties::default(),
+            audio: AudioProperties::default(),
+            speed: 1.0,
+            reversed: false,
+            freeze_frame: None,
+            time_remap: None,
+            link: None,
             enabled: true,
             locked: false,
         }
@@ -39,4 +206,354 @@ impl Clip {
         self.timeline_range = range;
         self
     }
+
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// Holds the source frame at `source_time` for the clip's whole length.
+    pub fn with_freeze_frame(mut self, source_time: RationalTime) -> Self {
+        self.freeze_frame = Some(source_time);
+        self.source_range = TimeRange::new(source_time, RationalTime::new(1, source_time.rate));
+        self
+    }
+
+    /// Drives the clip from a curve of source time, in seconds from the
+    /// start of the media, against the offset into the clip.
+    pub fn with_time_remap(mut self, curve: AnimatedValue) -> Self {
+        self.time_remap = Some(curve);
+        self.source_range = self.consumed_source_range();
+        self
+    }
+
+    pub fn is_retimed(&self) -> bool {
+        self.speed != 1.0 || self.reversed || self.freeze_frame.is_some() || self.time_remap.is_some()
+    }
+
+    /// Changes the constant speed, keeping the source media and stretching
+    /// or squeezing the clip's length on the timeline to fit.
+    pub fn set_speed(&mut self, speed: f64) -> Result<(), TimelineError> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(TimelineError::InvalidState(format!("speed must be positive, got {}", speed)));
+        }
+        let (numerator, denominator) = speed_ratio(speed);
+        let duration = self.source_range.duration.scaled(denominator, numerator, self.timeline_range.start.rate);
+        if duration.value <= 0 {
+            return Err(TimelineError::InvalidTimeRange {
+                start: self.timeline_range.start.value,
+                end: self.timeline_range.start.value,
+            });
+        }
+        self.speed = speed;
+        self.timeline_range = TimeRange::new(self.timeline_range.start, duration);
+        self.source_range = TimeRange::new(self.source_range.start, self.source_offset(duration));
+        Ok(())
+    }
+
+    /// How much source media plays during `timeline_offset` at the clip's
+    /// constant speed, rounded to the nearest source frame.
+    pub fn source_offset(&self, timeline_offset: RationalTime) -> RationalTime {
+        if self.speed == 1.0 {
+            timeline_offset
+        } else {
+            let (numerator, denominator) = speed_ratio(self.speed);
+            timeline_offset.scaled(numerator, denominator, self.source_range.start.rate)
+        }
+    }
+
+    /// The source frame shown at timeline time `time`. Freeze frames win
+    /// over time remapping, which wins over constant speed and reverse.
+    pub fn source_time_at(&self, time: &RationalTime) -> RationalTime {
+        let offset = *time - self.timeline_range.start;
+        let rate = self.source_range.start.rate;
+        if let Some(frame) = self.freeze_frame {
+            return frame;
+        }
+        if let Some(curve) = &self.time_remap {
+            return RationalTime::from_seconds(curve.at(&offset), rate);
+        }
+        if self.reversed {
+            self.source_range.end() - RationalTime::new(1, rate) - self.source_offset(offset)
+        } else {
+            self.source_range.start + self.source_offset(offset)
+        }
+    }
+
+    /// Where source time `source_time` first appears on the timeline, if
+    /// that part of the media is used by the clip.
+    pub fn timeline_time_of(&self, source_time: &RationalTime) -> Option<RationalTime> {
+        if !self.source_range.contains(source_time) {
+            return None;
+        }
+        let rate = self.timeline_range.start.rate;
+        if self.freeze_frame.is_some() {
+            return Some(self.timeline_range.start);
+        }
+        if self.time_remap.is_some() {
+            let frame = RationalTime::new(1, self.source_range.start.rate);
+            return self.timeline_frames().find(|time| {
+                let shown = self.source_time_at(time);
+                shown <= *source_time && *source_time < shown + frame
+            });
+        }
+        let offset = if self.reversed {
+            self.source_range.end() - RationalTime::new(1, source_time.rate) - *source_time
+        } else {
+            *source_time - self.source_range.start
+        };
+        let offset = if self.speed == 1.0 {
+            offset
+        } else {
+            let (numerator, denominator) = speed_ratio(self.speed);
+            offset.scaled(denominator, numerator, rate)
+        };
+        Some(self.timeline_range.start + offset)
+    }
+
+    /// Moves the clip's timeline in point to `start` and sets its length to
+    /// `duration`, moving the source range so the frames that stay on screen
+    /// are unchanged. Reversed clips give up source from the end as their
+    /// head is trimmed, and remapped clips shift their curve.
+    pub fn trim(&mut self, start: RationalTime, duration: RationalTime) -> Result<(), TimelineError> {
+        let invalid = || TimelineError::InvalidTimeRange {
+            start: start.value,
+            end: (start + duration).rescaled(start.rate).value,
+        };
+        if duration.value <= 0 {
+            return Err(invalid());
+        }
+        let head = start - self.timeline_range.start;
+        let tail = self.timeline_range.end() - (start + duration);
+
+        if let Some(curve) = &mut self.time_remap {
+            for keyframe in &mut curve.keyframes {
+                keyframe.time = keyframe.time - head;
+            }
+        } else if self.freeze_frame.is_none() {
+            let moved = if self.reversed { tail } else { head };
+            let source_start = self.source_range.start + self.source_offset(moved);
+            if source_start.value < 0 {
+                return Err(TimelineError::InsufficientMedia(self.id));
+            }
+            self.source_range = TimeRange::new(source_start, self.source_offset(duration));
+        }
+        self.timeline_range = TimeRange::new(start, duration);
+        if self.time_remap.is_some() {
+            self.source_range = self.consumed_source_range();
+        }
+        Ok(())
+    }
+
+    /// Shifts which source media the clip shows without moving it on the
+    /// timeline.
+    pub fn slip(&mut self, delta: RationalTime) {
+        self.source_range = self.source_range.shifted_by(delta);
+        if let Some(frame) = &mut self.freeze_frame {
+            *frame = *frame + delta;
+        }
+        if let Some(curve) = &mut self.time_remap {
+            let seconds = delta.to_seconds();
+            curve.value += seconds;
+            for keyframe in &mut curve.keyframes {
+                if let ParameterValue::Number(n) = &mut keyframe.value {
+                    *n += seconds;
+                }
+            }
+        }
+    }
+
+    /// Cuts the clip at timeline time `at`. `self` keeps the head and the
+    /// tail is returned as a new clip with its own id. Together the halves
+    /// use exactly the source media of the original. A linked tail joins
+    /// the tails of its group cut at the same time.
+    pub fn split(&mut self, at: RationalTime) -> Result<Clip, TimelineError> {
+        let (head, tail) = self.timeline_range.split_at(&at).ok_or(TimelineError::InvalidTimeRange {
+            start: at.value,
+            end: self.timeline_range.end().rescaled(at.rate).value,
+        })?;
+        let mut right = self.clone();
+        right.id = Uuid::new_v4();
+        right.link = self.link.map(|link| link.split_at(&at));
+
+        if self.time_remap.is_some() {
+            right.trim(tail.start, tail.duration)?;
+            self.trim(head.start, head.duration)?;
+            return Ok(right);
+        }
+        if self.freeze_frame.is_none() {
+            let played = self.source_offset(head.duration);
+            let remaining = self.source_range.duration - played;
+            let (left_start, right_start) = if self.reversed {
+                (self.source_range.start + remaining, self.source_range.start)
+            } else {
+                (self.source_range.start, self.source_range.start + played)
+            };
+            right.source_range = TimeRange::new(right_start, remaining);
+            self.source_range = TimeRange::new(left_start, played);
+        }
+        right.timeline_range = tail;
+        self.timeline_range = head;
+        Ok(right)
+    }
+
+    /// The source media the clip actually shows, from its earliest to its
+    /// latest frame.
+    pub fn consumed_source_range(&self) -> TimeRange {
+        let rate = self.source_range.start.rate;
+        if let Some(frame) = self.freeze_frame {
+            return TimeRange::new(frame, RationalTime::new(1, rate));
+        }
+        if self.time_remap.is_none() {
+            let start = self.source_range.start;
+            return TimeRange::new(start, self.source_offset(self.timeline_range.duration));
+        }
+        let shown: Vec<_> = self.timeline_frames().map(|time| self.source_time_at(&time)).collect();
+        match (shown.iter().min(), shown.iter().max()) {
+            (Some(first), Some(last)) => TimeRange::from_start_end(*first, *last + RationalTime::new(1, rate)),
+            _ => TimeRange::new(self.source_range.start, RationalTime::new(0, rate)),
+        }
+    }
+
+    /// Source media available past the clip's `edge` for extending it by
+    /// `extension` of timeline time, as `(required, available)`. `None` when
+    /// no extra media is needed: freeze frames and remapped clips hold their
+    /// end frames.
+    pub(crate) fn handle(&self, edge: TrimEdge, extension: RationalTime) -> Option<(RationalTime, RationalTime)> {
+        if self.freeze_frame.is_some() || self.time_remap.is_some() {
+            return None;
+        }
+        let required = self.source_offset(extension);
+        let media = self.source.available_range();
+        let before_start = match media {
+            Some(range) => self.source_range.start - range.start,
+            None => self.source_range.start,
+        };
+        let after_end = media.map(|range| range.end() - self.source_range.end());
+        let available = match (edge, self.reversed) {
+            (TrimEdge::Start, false) | (TrimEdge::End, true) => before_start,
+            (TrimEdge::End, false) | (TrimEdge::Start, true) => after_end?,
+        };
+        Some((required, available))
+    }
+
+    fn timeline_frames(&self) -> impl Iterator<Item = RationalTime> + '_ {
+        let start = self.timeline_range.start;
+        let frames = self.timeline_range.duration.rescaled(start.rate).value;
+        (0..frames).map(move |frame| start + RationalTime::new(frame, start.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Interpolation, Keyframe};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// Source frames 48 to 144 played from frame 100 of the timeline.
+    fn clip() -> Clip {
+        Clip::new("shot", "shot.mov", range(48, 144), range(100, 196))
+    }
+
+    #[test]
+    fn source_time_follows_constant_speed() {
+        let mut clip = clip();
+        assert_eq!(clip.source_time_at(&frames(110)), frames(58));
+
+        clip.set_speed(2.0).unwrap();
+        assert_eq!(clip.timeline_range, range(100, 148));
+        assert_eq!(clip.source_time_at(&frames(110)), frames(68));
+        assert_eq!(clip.timeline_time_of(&frames(68)), Some(frames(110)));
+
+        clip.set_speed(0.5).unwrap();
+        assert_eq!(clip.timeline_range, range(100, 292));
+        assert_eq!(clip.source_time_at(&frames(110)), frames(53));
+        assert!(clip.set_speed(0.0).is_err());
+    }
+
+    #[test]
+    fn fractional_speeds_retime_exactly() {
+        let mut clip = Clip::new("shot", "shot.mov", range(0, 1000), range(0, 1000));
+        clip.set_speed(1000.0 / 1001.0).unwrap();
+        assert_eq!(clip.timeline_range, range(0, 1001));
+        assert_eq!(clip.source_offset(frames(1001)), frames(1000));
+
+        clip.set_speed(1.0 / 3.0).unwrap();
+        assert_eq!(clip.timeline_range, range(0, 3000));
+        assert_eq!(clip.source_time_at(&frames(2999)), frames(1000));
+        assert_eq!(clip.source_time_at(&frames(1500)), frames(500));
+    }
+
+    #[test]
+    fn reversed_clips_play_from_the_end() {
+        let clip = clip().with_reversed(true);
+        assert_eq!(clip.source_time_at(&frames(100)), frames(143));
+        assert_eq!(clip.source_time_at(&frames(110)), frames(133));
+        assert_eq!(clip.timeline_time_of(&frames(133)), Some(frames(110)));
+        assert_eq!(clip.timeline_time_of(&frames(20)), None);
+    }
+
+    #[test]
+    fn freeze_frames_hold_one_frame() {
+        let clip = clip().with_freeze_frame(frames(60));
+        assert_eq!(clip.source_range, range(60, 61));
+        assert_eq!(clip.source_time_at(&frames(100)), frames(60));
+        assert_eq!(clip.source_time_at(&frames(195)), frames(60));
+        assert_eq!(clip.timeline_time_of(&frames(60)), Some(frames(100)));
+    }
+
+    #[test]
+    fn remapped_clips_follow_their_curve() {
+        let mut curve = AnimatedValue::new(2.0);
+        curve.set_keyframe(Keyframe::new(frames(0), 2.0, Interpolation::Linear));
+        curve.set_keyframe(Keyframe::new(frames(48), 4.0, Interpolation::Linear));
+        let clip = Clip::new("shot", "shot.mov", range(0, 1), range(100, 148)).with_time_remap(curve);
+
+        assert_eq!(clip.source_time_at(&frames(100)), frames(48));
+        assert_eq!(clip.source_time_at(&frames(124)), frames(72));
+        assert_eq!(clip.source_range, range(48, 96));
+        assert_eq!(clip.timeline_time_of(&frames(72)), Some(frames(124)));
+    }
+
+    #[test]
+    fn splitting_a_retimed_clip_shares_its_source() {
+        let mut fast = clip().with_speed(2.0).with_timeline_range(range(100, 148));
+        let tail = fast.split(frames(112)).unwrap();
+        assert_eq!((fast.source_range, fast.timeline_range), (range(48, 72), range(100, 112)));
+        assert_eq!((tail.source_range, tail.timeline_range), (range(72, 144), range(112, 148)));
+        assert_eq!(tail.source_time_at(&frames(112)), frames(72));
+
+        let mut backwards = clip().with_reversed(true);
+        let tail = backwards.split(frames(124)).unwrap();
+        assert_eq!(backwards.source_range, range(120, 144));
+        assert_eq!(tail.source_range, range(48, 120));
+        assert_eq!(tail.source_time_at(&frames(124)), frames(119));
+    }
+
+    #[test]
+    fn trimming_a_retimed_clip_keeps_the_frames_on_screen() {
+        let mut fast = clip().with_speed(2.0).with_timeline_range(range(100, 148));
+        let shown = fast.source_time_at(&frames(110));
+        fast.trim(frames(106), frames(42)).unwrap();
+        assert_eq!(fast.source_range, range(60, 144));
+        assert_eq!(fast.source_time_at(&frames(110)), shown);
+
+        let mut backwards = clip().with_reversed(true);
+        let shown = backwards.source_time_at(&frames(130));
+        backwards.trim(frames(124), frames(72)).unwrap();
+        assert_eq!(backwards.source_range, range(48, 120));
+        assert_eq!(backwards.source_time_at(&frames(130)), shown);
+    }
 }
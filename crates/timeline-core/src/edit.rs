@@ -0,0 +1,931 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::track::{check_overlaps, no_clips_error};
+use crate::{Caption, Clip, ClipLink, MediaSource, RationalTime, TimeRange, Timeline, TimelineError, Track};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TrimEdge {
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClipPlacement {
+    pub source_range: TimeRange,
+    pub timeline_range: TimeRange,
+}
+
+impl ClipPlacement {
+    fn of(clip: &Clip) -> Self {
+        Self {
+            source_range: clip.source_range,
+            timeline_range: clip.timeline_range,
+        }
+    }
+}
+
+/// One clip touched by an edit. `before` is `None` for clips the edit
+/// created and `after` is `None` for clips it removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipChange {
+    pub track_id: Uuid,
+    pub clip_id: Uuid,
+    pub before: Option<ClipPlacement>,
+    pub after: Option<ClipPlacement>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EditResult {
+    pub changes: Vec<ClipChange>,
+}
+
+impl EditResult {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn merge(&mut self, other: EditResult) {
+        self.changes.extend(other.changes);
+    }
+}
+
+/// The four marks of a three- or four-point edit. Any one may be left
+/// unset and is derived from the other three; with all four set the source
+/// is retimed to fill the record range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EditPoints {
+    pub source_in: Option<RationalTime>,
+    pub source_out: Option<RationalTime>,
+    pub record_in: Option<RationalTime>,
+    pub record_out: Option<RationalTime>,
+}
+
+impl EditPoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source(mut self, source_in: RationalTime, source_out: RationalTime) -> Self {
+        self.source_in = Some(source_in);
+        self.source_out = Some(source_out);
+        self
+    }
+
+    pub fn with_record(mut self, record_in: RationalTime, record_out: RationalTime) -> Self {
+        self.record_in = Some(record_in);
+        self.record_out = Some(record_out);
+        self
+    }
+
+    /// Returns the source range, record range and playback speed.
+    pub fn resolve(&self) -> Result<(TimeRange, TimeRange, f64), TimelineError> {
+        let (source, record) = match (self.source_in, self.source_out, self.record_in, self.record_out) {
+            (Some(si), Some(so), Some(ri), Some(ro)) => {
+                (TimeRange::from_start_end(si, so), TimeRange::from_start_end(ri, ro))
+            }
+            (None, Some(so), Some(ri), Some(ro)) => {
+                let record = TimeRange::from_start_end(ri, ro);
+                (TimeRange::new(so - record.duration, record.duration), record)
+            }
+            (Some(si), None, Some(ri), Some(ro)) => {
+                let record = TimeRange::from_start_end(ri, ro);
+                (TimeRange::new(si, record.duration), record)
+            }
+            (Some(si), Some(so), None, Some(ro)) => {
+                let source = TimeRange::from_start_end(si, so);
+                (source, TimeRange::new(ro - source.duration, source.duration))
+            }
+            (Some(si), Some(so), Some(ri), None) => {
+                let source = TimeRange::from_start_end(si, so);
+                (source, TimeRange::new(ri, source.duration))
+            }
+            _ => {
+                return Err(TimelineError::InvalidState(
+                    "an edit needs at least three of source in/out and record in/out".to_string(),
+                ))
+            }
+        };
+        if source.is_empty() || record.is_empty() {
+            return Err(TimelineError::InvalidTimeRange {
+                start: record.start.value,
+                end: record.end().rescaled(record.start.rate).value,
+            });
+        }
+        let speed = source.duration.to_seconds() / record.duration.to_seconds();
+        Ok((source, record, speed))
+    }
+}
+
+impl Track {
+    /// Runs `edit` against a copy of the clip list and only commits it if
+    /// the result is valid: no locked clip was touched, every changed clip
+    /// still has positive duration inside its available media, and no two
+    /// clips overlap.
+    pub(crate) fn apply_edit<F>(&mut self, edit: F) -> Result<EditResult, TimelineError>
+    where
+        F: FnOnce(&mut Vec<Clip>) -> Result<(), TimelineError>,
+    {
+        if self.locked {
+            return Err(TimelineError::TrackLocked(self.id));
+        }
+
+        let mut clips = self.clips.clone();
+        edit(&mut clips)?;
+        if !clips.is_empty() && !self.kind.holds_clips() {
+            return Err(no_clips_error(self));
+        }
+
+        let mut changes = Vec::new();
+        for before in &self.clips {
+            let after = clips.iter().find(|c| c.id == before.id);
+            if after.map(ClipPlacement::of) != Some(ClipPlacement::of(before)) {
+                if before.locked {
+                    return Err(TimelineError::ClipLocked(before.id));
+                }
+                changes.push(ClipChange {
+                    track_id: self.id,
+                    clip_id: before.id,
+                    before: Some(ClipPlacement::of(before)),
+                    after: after.map(ClipPlacement::of),
+                });
+            }
+        }
+        for added in clips.iter().filter(|c| !self.clips.iter().any(|b| b.id == c.id)) {
+            changes.push(ClipChange {
+                track_id: self.id,
+                clip_id: added.id,
+                before: None,
+                after: Some(ClipPlacement::of(added)),
+            });
+        }
+
+        for change in changes.iter().filter(|c| c.after.is_some()) {
+            let clip = clips.iter().find(|c| c.id == change.clip_id).unwrap();
+            validate_clip(clip)?;
+        }
+        clips.sort_by_key(|c| c.timeline_range.start);
+        check_overlaps(&clips)?;
+
+        let previous = std::mem::replace(&mut self.clips, clips);
+        let transitions = self.transitions.clone();
+        if let Err(err) = self.reconcile_transitions(&previous) {
+            self.clips = previous;
+            self.transitions = transitions;
+            return Err(err);
+        }
+        Ok(EditResult { changes })
+    }
+
+    /// Trims one edge of a clip by `delta` and shifts every later clip to
+    /// close (or open) the resulting gap. A positive delta moves the edge
+    /// later in time.
+    pub fn ripple_trim(&mut self, clip_id: Uuid, edge: TrimEdge, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            let clip = find(clips, clip_id)?;
+            let TimeRange { start, duration } = clip.timeline_range;
+            let old_end = clip.timeline_range.end();
+            let shift = match edge {
+                TrimEdge::Start => {
+                    clip.trim(start + delta, duration - delta)?;
+                    clip.timeline_range.start = start;
+                    -delta
+                }
+                TrimEdge::End => {
+                    clip.trim(start, duration + delta)?;
+                    delta
+                }
+            };
+            for other in clips.iter_mut().filter(|c| c.id != clip_id && c.timeline_range.start >= old_end) {
+                other.timeline_range = other.timeline_range.shifted_by(shift);
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves the edit point between a clip and the clip directly after it,
+    /// lengthening one and shortening the other so nothing else moves.
+    pub fn roll_edit(&mut self, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            let edit_point = find(clips, clip_id)?.timeline_range.end();
+            let next_id = adjacent_after(clips, edit_point).ok_or_else(|| {
+                TimelineError::InvalidState(format!("clip {} has no adjacent clip to roll into", clip_id))
+            })?;
+
+            trim_tail(find(clips, clip_id)?, delta)?;
+            trim_head(find(clips, next_id)?, delta)
+        })
+    }
+
+    /// Shifts which part of the source a clip shows without moving it on
+    /// the timeline.
+    pub fn slip(&mut self, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            find(clips, clip_id)?.slip(delta);
+            Ok(())
+        })
+    }
+
+    /// Moves a clip along the timeline by `delta`, leaving its neighbours
+    /// where they are.
+    pub fn move_clip(&mut self, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            let clip = find(clips, clip_id)?;
+            clip.timeline_range = clip.timeline_range.shifted_by(delta);
+            Ok(())
+        })
+    }
+
+    /// Moves a clip along the timeline, lengthening the clip on one side
+    /// and shortening the clip on the other so the track length is kept.
+    pub fn slide(&mut self, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            let range = find(clips, clip_id)?.timeline_range;
+            let prev_id = adjacent_before(clips, range.start);
+            let next_id = adjacent_after(clips, range.end());
+
+            let clip = find(clips, clip_id)?;
+            clip.timeline_range = clip.timeline_range.shifted_by(delta);
+
+            if let Some(prev_id) = prev_id {
+                trim_tail(find(clips, prev_id)?, delta)?;
+            }
+            if let Some(next_id) = next_id {
+                trim_head(find(clips, next_id)?, delta)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Track {
+    /// Cuts a clip in two at `at`, returning the id of the new right-hand clip.
+    pub fn split_clip(&mut self, clip_id: Uuid, at: RationalTime) -> Result<Uuid, TimelineError> {
+        let mut tail_id = clip_id;
+        self.apply_edit(|clips| {
+            let tail = find(clips, clip_id)?.split(at)?;
+            tail_id = tail.id;
+            clips.push(tail);
+            Ok(())
+        })?;
+        Ok(tail_id)
+    }
+
+    /// Places `clip` at its timeline range, pushing everything from its
+    /// start onwards later by its duration. A clip straddling the insert
+    /// point is split.
+    pub fn insert_clip(&mut self, clip: Clip) -> Result<EditResult, TimelineError> {
+        let range = clip.timeline_range;
+        let changes = self.apply_edit(|clips| {
+            open_gap(clips, range)?;
+            clips.push(clip);
+            Ok(())
+        })?;
+        self.open_caption_gap(range);
+        Ok(changes)
+    }
+
+    /// Opens up `range` as a gap, pushing everything from its start onwards
+    /// later by its duration. A clip or caption straddling the start is
+    /// split.
+    pub fn insert_gap(&mut self, range: TimeRange) -> Result<EditResult, TimelineError> {
+        let changes = self.apply_edit(|clips| open_gap(clips, range))?;
+        self.open_caption_gap(range);
+        Ok(changes)
+    }
+
+    /// Places `clip` at its timeline range, cutting away whatever was
+    /// underneath it. Nothing outside that range moves.
+    pub fn overwrite_clip(&mut self, clip: Clip) -> Result<EditResult, TimelineError> {
+        let range = clip.timeline_range;
+        self.apply_edit(|clips| {
+            split_clips_at(clips, range.start)?;
+            split_clips_at(clips, range.end())?;
+            clips.retain(|c| !range.contains_range(&c.timeline_range));
+            clips.push(clip);
+            Ok(())
+        })
+    }
+
+    /// Removes the material inside `range`, splitting clips and captions
+    /// that straddle its edges, and leaves a gap behind. Returns the
+    /// removed clips.
+    pub fn lift_range(&mut self, range: TimeRange) -> Result<Vec<Clip>, TimelineError> {
+        self.cut_range(range, false).map(|(_, removed)| removed)
+    }
+
+    /// Like [`Track::lift_range`] but ripples later clips and captions back
+    /// to close the gap.
+    pub fn extract_range(&mut self, range: TimeRange) -> Result<Vec<Clip>, TimelineError> {
+        self.cut_range(range, true).map(|(_, removed)| removed)
+    }
+
+    fn cut_range(&mut self, range: TimeRange, ripple: bool) -> Result<(EditResult, Vec<Clip>), TimelineError> {
+        let mut removed = Vec::new();
+        let changes = self.apply_edit(|clips| {
+            split_clips_at(clips, range.start)?;
+            split_clips_at(clips, range.end())?;
+            let (inside, outside) = clips.drain(..).partition(|c| range.contains_range(&c.timeline_range));
+            removed = inside;
+            *clips = outside;
+            if ripple {
+                for other in clips.iter_mut().filter(|c| c.timeline_range.start >= range.end()) {
+                    other.timeline_range = other.timeline_range.shifted_by(-range.duration);
+                }
+            }
+            Ok(())
+        })?;
+        self.cut_captions(range, ripple);
+        removed.sort_by_key(|c| c.timeline_range.start);
+        Ok((changes, removed))
+    }
+}
+
+impl Timeline {
+    fn edit_track(&mut self, track_id: Uuid) -> Result<&mut Track, TimelineError> {
+        self.track_mut(track_id).ok_or(TimelineError::TrackNotFound(track_id))
+    }
+
+    // Edits made through the timeline carry every clip linked to `clip_id`
+    // along with it.
+
+    pub fn move_clip(&mut self, track_id: Uuid, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.edit_linked(track_id, clip_id, |track, id| track.move_clip(id, delta))
+    }
+
+    /// Captions can't be linked, so unlocked subtitle tracks ripple along
+    /// with the trimmed clip to stay in sync with the picture.
+    pub fn ripple_trim(&mut self, track_id: Uuid, clip_id: Uuid, edge: TrimEdge, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        let result = self.edit_linked(track_id, clip_id, |track, id| track.ripple_trim(id, edge, delta))?;
+        let trimmed = result
+            .changes
+            .iter()
+            .find(|c| c.clip_id == clip_id)
+            .and_then(|c| Some((c.before?.timeline_range.end(), c.after?.timeline_range.end())));
+        if let Some((old_end, new_end)) = trimmed {
+            let subtitles = self.rippled_subtitle_tracks(&[]);
+            for track in self.tracks.iter_mut().filter(|t| subtitles.contains(&t.id)) {
+                if new_end < old_end {
+                    track.cut_captions(TimeRange::from_start_end(new_end, old_end), true);
+                } else if new_end > old_end {
+                    track.open_caption_gap(TimeRange::from_start_end(old_end, new_end));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn roll_edit(&mut self, track_id: Uuid, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.edit_linked(track_id, clip_id, |track, id| track.roll_edit(id, delta))
+    }
+
+    pub fn slip(&mut self, track_id: Uuid, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.edit_linked(track_id, clip_id, |track, id| track.slip(id, delta))
+    }
+
+    pub fn slide(&mut self, track_id: Uuid, clip_id: Uuid, delta: RationalTime) -> Result<EditResult, TimelineError> {
+        self.edit_linked(track_id, clip_id, |track, id| track.slide(id, delta))
+    }
+
+    // Range edits also reach the tracks of clips linked to the material
+    // they move or remove, so no group is left behind out of sync. Rippling
+    // edits carry unlocked subtitle tracks along the same way.
+
+    /// Three- or four-point insert of `source` onto every track in
+    /// `track_ids`, rippling downstream material on those tracks. Tracks
+    /// of clips linked to rippled ones get a matching gap. The new clips
+    /// are linked when there is more than one.
+    pub fn insert_edit(&mut self, source: MediaSource, points: EditPoints, track_ids: &[Uuid]) -> Result<EditResult, TimelineError> {
+        let (_, record_range, _) = points.resolve()?;
+        let mut tracks = self.linked_tracks(track_ids, |c| c.timeline_range.end() > record_range.start);
+        tracks.extend(self.rippled_subtitle_tracks(&tracks));
+        self.point_edit(source, points, track_ids, &tracks[track_ids.len()..], Track::insert_clip, Track::insert_gap)
+    }
+
+    /// Three- or four-point overwrite of `source` onto every track in
+    /// `track_ids`, replacing whatever was in the record range. Clips
+    /// linked to replaced ones are cleared from the same range. The new
+    /// clips are linked when there is more than one.
+    pub fn overwrite_edit(&mut self, source: MediaSource, points: EditPoints, track_ids: &[Uuid]) -> Result<EditResult, TimelineError> {
+        let (_, record_range, _) = points.resolve()?;
+        let tracks = self.linked_tracks(track_ids, |c| c.timeline_range.overlaps(&record_range));
+        self.point_edit(source, points, track_ids, &tracks[track_ids.len()..], Track::overwrite_clip, |track, range| {
+            track.cut_range(range, false).map(|(changes, _)| changes)
+        })
+    }
+
+    /// Removes `range` from each track in `track_ids`, and from the tracks
+    /// of clips linked to removed ones, leaving a gap. Returns the removed
+    /// material as a timeline starting at zero.
+    pub fn lift(&mut self, range: TimeRange, track_ids: &[Uuid]) -> Result<Timeline, TimelineError> {
+        let tracks = self.linked_tracks(track_ids, |c| c.timeline_range.overlaps(&range));
+        self.cut(range, &tracks, Track::lift_range)
+    }
+
+    /// Removes `range` from each track in `track_ids` and closes the gap.
+    /// Every listed track, and every track of a clip linked to removed or
+    /// rippled ones, ripples by the same amount so they stay in sync.
+    pub fn extract(&mut self, range: TimeRange, track_ids: &[Uuid]) -> Result<Timeline, TimelineError> {
+        let mut tracks = self.linked_tracks(track_ids, |c| c.timeline_range.end() > range.start);
+        tracks.extend(self.rippled_subtitle_tracks(&tracks));
+        self.cut(range, &tracks, Track::extract_range)
+    }
+
+    fn cut<F>(&mut self, range: TimeRange, track_ids: &[Uuid], remove: F) -> Result<Timeline, TimelineError>
+    where
+        F: Fn(&mut Track, TimeRange) -> Result<Vec<Clip>, TimelineError>,
+    {
+        let mut clipboard = Timeline::new(self.name.clone(), self.metadata.frame_rate)
+            .with_metadata(self.metadata.clone());
+
+        let snapshot = self.tracks.clone();
+        for &track_id in track_ids {
+            let removed = self.edit_track(track_id).and_then(|track| remove(track, range));
+            let removed = match removed {
+                Ok(removed) => removed,
+                Err(err) => {
+                    self.tracks = snapshot;
+                    return Err(err);
+                }
+            };
+
+            let source = &snapshot.iter().find(|t| t.id == track_id).unwrap();
+            let mut track = Track::new(source.name.clone(), source.kind);
+            for mut clip in removed {
+                clip.timeline_range = clip.timeline_range.shifted_by(-range.start);
+                track.add_clip(clip)?;
+            }
+            for caption in source.captions_in_range(&range) {
+                let visible = caption.range.intersection(&range).unwrap();
+                let caption = Caption { range: visible.shifted_by(-range.start), ..caption.clone() };
+                track.add_caption(caption)?;
+            }
+            clipboard.add_track(track);
+        }
+        Ok(clipboard)
+    }
+
+    /// Unlocked subtitle tracks not already in `track_ids`.
+    fn rippled_subtitle_tracks(&self, track_ids: &[Uuid]) -> Vec<Uuid> {
+        self.subtitle_tracks()
+            .filter(|t| !t.locked && !track_ids.contains(&t.id))
+            .map(|t| t.id)
+            .collect()
+    }
+
+    /// Places a clip of `source` on each of `track_ids` and makes room for
+    /// it on each of `linked_ids`.
+    fn point_edit<F, G>(
+        &mut self,
+        source: MediaSource,
+        points: EditPoints,
+        track_ids: &[Uuid],
+        linked_ids: &[Uuid],
+        place: F,
+        make_room: G,
+    ) -> Result<EditResult, TimelineError>
+    where
+        F: Fn(&mut Track, Clip) -> Result<EditResult, TimelineError>,
+        G: Fn(&mut Track, TimeRange) -> Result<EditResult, TimelineError>,
+    {
+        let (source_range, record_range, speed) = points.resolve()?;
+        let name = Path::new(&source.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| source.path.clone());
+
+        let group_id = (track_ids.len() > 1).then(Uuid::new_v4);
+        let snapshot = self.tracks.clone();
+        let mut result = EditResult::default();
+        for &track_id in track_ids {
+            let mut clip = Clip::new(name.clone(), source.clone(), source_range, record_range).with_speed(speed);
+            clip.link = group_id.map(|group_id| ClipLink { group_id, anchor: clip.sync_point() });
+            match self.edit_track(track_id).and_then(|track| place(track, clip)) {
+                Ok(changes) => result.merge(changes),
+                Err(err) => {
+                    self.tracks = snapshot;
+                    return Err(err);
+                }
+            }
+        }
+        for &track_id in linked_ids {
+            match self.edit_track(track_id).and_then(|track| make_room(track, record_range)) {
+                Ok(changes) => result.merge(changes),
+                Err(err) => {
+                    self.tracks = snapshot;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn find(clips: &mut [Clip], clip_id: Uuid) -> Result<&mut Clip, TimelineError> {
+    clips
+        .iter_mut()
+        .find(|c| c.id == clip_id)
+        .ok_or(TimelineError::ClipNotFound(clip_id))
+}
+
+fn adjacent_before(clips: &[Clip], time: RationalTime) -> Option<Uuid> {
+    clips.iter().find(|c| c.timeline_range.end() == time).map(|c| c.id)
+}
+
+fn adjacent_after(clips: &[Clip], time: RationalTime) -> Option<Uuid> {
+    clips.iter().find(|c| c.timeline_range.start == time).map(|c| c.id)
+}
+
+/// Splits the clip straddling the start of `range` and pushes everything
+/// from there on later by its duration.
+fn open_gap(clips: &mut Vec<Clip>, range: TimeRange) -> Result<(), TimelineError> {
+    split_clips_at(clips, range.start)?;
+    for other in clips.iter_mut().filter(|c| c.timeline_range.start >= range.start) {
+        other.timeline_range = other.timeline_range.shifted_by(range.duration);
+    }
+    Ok(())
+}
+
+/// Splits whichever clip straddles `at` so that `at` becomes an edit point.
+fn split_clips_at(clips: &mut Vec<Clip>, at: RationalTime) -> Result<(), TimelineError> {
+    if let Some(clip) = clips.iter_mut().find(|c| c.timeline_range.split_at(&at).is_some()) {
+        let tail = clip.split(at)?;
+        clips.push(tail);
+    }
+    Ok(())
+}
+
+/// Moves a clip's in point later by `delta`, keeping its out point fixed.
+fn trim_head(clip: &mut Clip, delta: RationalTime) -> Result<(), TimelineError> {
+    let TimeRange { start, duration } = clip.timeline_range;
+    clip.trim(start + delta, duration - delta)
+}
+
+/// Moves a clip's out point later by `delta`, keeping its in point fixed.
+fn trim_tail(clip: &mut Clip, delta: RationalTime) -> Result<(), TimelineError> {
+    let TimeRange { start, duration } = clip.timeline_range;
+    clip.trim(start, duration + delta)
+}
+
+fn validate_clip(clip: &Clip) -> Result<(), TimelineError> {
+    if clip.timeline_range.is_empty() || clip.source_range.is_empty() {
+        return Err(TimelineError::InvalidTimeRange {
+            start: clip.timeline_range.start.value,
+            end: clip.timeline_range.end().rescaled(clip.timeline_range.start.rate).value,
+        });
+    }
+    let within_media = match clip.source.available_range() {
+        Some(available) => available.contains_range(&clip.source_range),
+        None => clip.source_range.start.value >= 0,
+    };
+    if !within_media {
+        return Err(TimelineError::InsufficientMedia(clip.id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MediaMetadata, TrackKind};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    fn clip(start: i64, end: i64) -> Clip {
+        Clip::new("shot", "shot.mov", range(0, end - start), range(start, end))
+    }
+
+    fn ranges(track: &Track) -> Vec<TimeRange> {
+        track.clips().iter().map(|c| c.timeline_range).collect()
+    }
+
+    fn sources(track: &Track) -> Vec<TimeRange> {
+        track.clips().iter().map(|c| c.source_range).collect()
+    }
+
+    /// Three back-to-back clips of 96 frames from 0, each showing frames
+    /// 48 to 144 of a 240 frame file so there are handles on both sides.
+    fn handled_track() -> (Track, [Uuid; 3]) {
+        let media = MediaSource::new("shot.mov").with_metadata(MediaMetadata { duration: frames(240), ..MediaMetadata::default() });
+        let mut track = Track::new("V1", TrackKind::Video);
+        let mut ids = [Uuid::nil(); 3];
+        for (index, id) in ids.iter_mut().enumerate() {
+            let start = index as i64 * 96;
+            let clip = Clip::new("shot", media.clone(), range(48, 144), range(start, start + 96));
+            *id = clip.id;
+            track.add_clip(clip).unwrap();
+        }
+        (track, ids)
+    }
+
+    #[test]
+    fn ripple_trim_end_pushes_later_clips() {
+        let (mut track, [a, _, _]) = handled_track();
+        let result = track.ripple_trim(a, TrimEdge::End, frames(24)).unwrap();
+        assert_eq!(result.changes.len(), 3);
+        assert_eq!(ranges(&track), vec![range(0, 120), range(120, 216), range(216, 312)]);
+        assert_eq!(track.clips()[0].source_range, range(48, 168));
+    }
+
+    #[test]
+    fn ripple_trim_start_keeps_the_clip_in_place() {
+        let (mut track, [_, b, _]) = handled_track();
+        track.ripple_trim(b, TrimEdge::Start, frames(24)).unwrap();
+        assert_eq!(ranges(&track), vec![range(0, 96), range(96, 168), range(168, 264)]);
+        assert_eq!(track.clips()[1].source_range, range(72, 144));
+
+        track.ripple_trim(b, TrimEdge::Start, frames(-48)).unwrap();
+        assert_eq!(track.clips()[1].source_range, range(24, 144));
+        assert_eq!(ranges(&track)[2], range(216, 312));
+    }
+
+    #[test]
+    fn ripple_trim_stops_at_the_media() {
+        let (mut track, [a, b, _]) = handled_track();
+        let before = track.clone();
+        assert!(matches!(track.ripple_trim(a, TrimEdge::End, frames(97)), Err(TimelineError::InsufficientMedia(id)) if id == a));
+        assert!(matches!(track.ripple_trim(b, TrimEdge::Start, frames(-49)), Err(TimelineError::InsufficientMedia(_))));
+        assert!(track.ripple_trim(b, TrimEdge::Start, frames(96)).is_err());
+        assert_eq!(track, before);
+    }
+
+    #[test]
+    fn roll_moves_only_the_edit_point() {
+        let (mut track, [a, b, c]) = handled_track();
+        track.roll_edit(a, frames(24)).unwrap();
+        assert_eq!(ranges(&track), vec![range(0, 120), range(120, 192), range(192, 288)]);
+        assert_eq!(sources(&track), vec![range(48, 168), range(72, 144), range(48, 144)]);
+
+        track.roll_edit(b, frames(-48)).unwrap();
+        assert_eq!(ranges(&track)[1..], [range(120, 144), range(144, 288)]);
+        assert_eq!(track.clips()[2].source_range, range(0, 144));
+
+        assert!(matches!(track.roll_edit(c, frames(1)), Err(TimelineError::InvalidState(_))));
+        assert!(matches!(track.roll_edit(b, frames(-1)), Err(TimelineError::InsufficientMedia(id)) if id == c));
+    }
+
+    #[test]
+    fn slip_changes_only_the_source() {
+        let (mut track, [_, b, _]) = handled_track();
+        track.slip(b, frames(24)).unwrap();
+        assert_eq!(ranges(&track)[1], range(96, 192));
+        assert_eq!(track.clips()[1].source_range, range(72, 168));
+
+        assert!(matches!(track.slip(b, frames(73)), Err(TimelineError::InsufficientMedia(_))));
+        assert!(matches!(track.slip(b, frames(-73)), Err(TimelineError::InsufficientMedia(_))));
+        assert_eq!(track.clips()[1].source_range, range(72, 168));
+    }
+
+    #[test]
+    fn slide_trims_the_neighbours() {
+        let (mut track, [_, b, _]) = handled_track();
+        track.slide(b, frames(24)).unwrap();
+        assert_eq!(ranges(&track), vec![range(0, 120), range(120, 216), range(216, 288)]);
+        assert_eq!(sources(&track), vec![range(48, 168), range(48, 144), range(72, 144)]);
+
+        // The clip after only has 72 frames of media before its in point.
+        assert!(matches!(track.slide(b, frames(-73)), Err(TimelineError::InsufficientMedia(_))));
+        assert_eq!(ranges(&track)[1], range(120, 216));
+    }
+
+    #[test]
+    fn locks_block_edits_that_touch_them() {
+        let (mut track, [a, b, c]) = handled_track();
+        track.update_clip(c, |clip| clip.locked = true).unwrap();
+        let before = track.clone();
+
+        assert!(matches!(track.ripple_trim(a, TrimEdge::End, frames(24)), Err(TimelineError::ClipLocked(id)) if id == c));
+        assert!(matches!(track.roll_edit(b, frames(24)), Err(TimelineError::ClipLocked(id)) if id == c));
+        assert!(matches!(track.slip(c, frames(1)), Err(TimelineError::ClipLocked(id)) if id == c));
+        assert_eq!(track, before);
+        // Edits that leave the locked clip alone still work.
+        track.roll_edit(a, frames(24)).unwrap();
+
+        track.locked = true;
+        assert!(matches!(track.slip(a, frames(1)), Err(TimelineError::TrackLocked(_))));
+    }
+
+    #[test]
+    fn three_point_edits_derive_the_missing_point() {
+        let cases = [
+            (EditPoints { source_in: None, ..full_points() }, range(24, 72), range(100, 148)),
+            (EditPoints { source_out: None, ..full_points() }, range(10, 58), range(100, 148)),
+            (EditPoints { record_in: None, ..full_points() }, range(10, 72), range(86, 148)),
+            (EditPoints { record_out: None, ..full_points() }, range(10, 72), range(100, 162)),
+        ];
+        for (points, source, record) in cases {
+            assert_eq!(points.resolve().unwrap(), (source, record, 1.0));
+        }
+    }
+
+    /// Source 10 to 72 against record 100 to 148.
+    fn full_points() -> EditPoints {
+        EditPoints::new().with_source(frames(10), frames(72)).with_record(frames(100), frames(148))
+    }
+
+    #[test]
+    fn four_point_edits_fit_the_source_to_the_record() {
+        let points = EditPoints::new().with_source(frames(0), frames(96)).with_record(frames(0), frames(48));
+        assert_eq!(points.resolve().unwrap(), (range(0, 96), range(0, 48), 2.0));
+
+        let two_points = EditPoints::new().with_record(frames(0), frames(48));
+        assert!(matches!(two_points.resolve(), Err(TimelineError::InvalidState(_))));
+        let empty = EditPoints::new().with_source(frames(10), frames(10)).with_record(frames(0), frames(48));
+        assert!(matches!(empty.resolve(), Err(TimelineError::InvalidTimeRange { .. })));
+    }
+
+    #[test]
+    fn extract_returns_the_removed_material_from_zero() {
+        let (track, _) = handled_track();
+        let track_id = track.id;
+        let mut timeline = Timeline::new("edit", 24);
+        timeline.add_track(track);
+
+        let clipboard = timeline.extract(range(72, 120), &[track_id]).unwrap();
+        let removed = &clipboard.tracks[0];
+        assert_eq!(removed.name, "V1");
+        assert_eq!(ranges(removed), vec![range(0, 24), range(24, 48)]);
+        assert_eq!(sources(removed), vec![range(120, 144), range(48, 72)]);
+        let track = timeline.track(track_id).unwrap();
+        assert_eq!(ranges(track), vec![range(0, 72), range(72, 144), range(144, 240)]);
+        assert_eq!(sources(track), vec![range(48, 120), range(72, 144), range(48, 144)]);
+    }
+
+    #[test]
+    fn lift_leaves_a_gap() {
+        let (track, _) = handled_track();
+        let track_id = track.id;
+        let mut timeline = Timeline::new("edit", 24);
+        timeline.add_track(track);
+
+        let clipboard = timeline.lift(range(72, 120), &[track_id]).unwrap();
+        assert_eq!(ranges(&clipboard.tracks[0]), vec![range(0, 24), range(24, 48)]);
+        let track = timeline.track(track_id).unwrap();
+        assert_eq!(ranges(track), vec![range(0, 72), range(120, 192), range(192, 288)]);
+        assert!(matches!(timeline.lift(range(0, 24), &[Uuid::new_v4()]), Err(TimelineError::TrackNotFound(_))));
+    }
+
+    /// A picture and sound clip linked together from 48 to 144 on V1 and A1.
+    fn linked_timeline() -> (Timeline, Uuid, Uuid) {
+        let mut timeline = Timeline::new("edit", 24);
+        let (video, audio) = (Track::new("V1", TrackKind::Video), Track::new("A1", TrackKind::Audio));
+        let (video_id, audio_id) = (video.id, audio.id);
+        timeline.add_track(video);
+        timeline.add_track(audio);
+        let (picture, sound) = (clip(48, 144), clip(48, 144));
+        let ids = [picture.id, sound.id];
+        timeline.add_clip_to_track(video_id, picture).unwrap();
+        timeline.add_clip_to_track(audio_id, sound).unwrap();
+        timeline.link_clips(&ids).unwrap();
+        (timeline, video_id, audio_id)
+    }
+
+    #[test]
+    fn insert_ripples_linked_clips_on_other_tracks() {
+        let (mut timeline, video_id, audio_id) = linked_timeline();
+        let points = EditPoints::new().with_source(frames(0), frames(24)).with_record(frames(60), frames(84));
+        timeline.insert_edit(MediaSource::new("insert.mov"), points, &[video_id]).unwrap();
+
+        assert_eq!(ranges(timeline.track(video_id).unwrap()), vec![range(48, 60), range(60, 84), range(84, 168)]);
+        assert_eq!(ranges(timeline.track(audio_id).unwrap()), vec![range(48, 60), range(84, 168)]);
+        assert!(timeline.sync_offsets().is_empty());
+    }
+
+    #[test]
+    fn overwrite_clears_linked_clips_under_the_record_range() {
+        let (mut timeline, video_id, audio_id) = linked_timeline();
+        let points = EditPoints::new().with_source(frames(0), frames(24)).with_record(frames(60), frames(84));
+        timeline.overwrite_edit(MediaSource::new("insert.mov"), points, &[video_id]).unwrap();
+
+        assert_eq!(ranges(timeline.track(video_id).unwrap()), vec![range(48, 60), range(60, 84), range(84, 144)]);
+        assert_eq!(ranges(timeline.track(audio_id).unwrap()), vec![range(48, 60), range(84, 144)]);
+        assert!(timeline.sync_offsets().is_empty());
+    }
+
+    #[test]
+    fn lift_and_extract_take_linked_clips_along() {
+        let (mut lifted, video_id, audio_id) = linked_timeline();
+        let clipboard = lifted.lift(range(96, 120), &[audio_id]).unwrap();
+        assert_eq!(clipboard.tracks.len(), 2);
+        assert_eq!(ranges(lifted.track(video_id).unwrap()), vec![range(48, 96), range(120, 144)]);
+        assert_eq!(ranges(lifted.track(audio_id).unwrap()), vec![range(48, 96), range(120, 144)]);
+
+        let (mut extracted, video_id, audio_id) = linked_timeline();
+        extracted.extract(range(96, 120), &[audio_id]).unwrap();
+        assert_eq!(ranges(extracted.track(video_id).unwrap()), vec![range(48, 96), range(96, 120)]);
+        assert_eq!(ranges(extracted.track(audio_id).unwrap()), vec![range(48, 96), range(96, 120)]);
+        assert!(extracted.sync_offsets().is_empty());
+    }
+
+    #[test]
+    fn locked_linked_track_blocks_range_edits() {
+        let (mut timeline, video_id, audio_id) = linked_timeline();
+        timeline.track_mut(audio_id).unwrap().locked = true;
+        let before = timeline.clone();
+        let points = EditPoints::new().with_source(frames(0), frames(24)).with_record(frames(0), frames(24));
+        let result = timeline.insert_edit(MediaSource::new("insert.mov"), points, &[video_id]);
+        assert!(matches!(result, Err(TimelineError::TrackLocked(id)) if id == audio_id));
+        assert!(matches!(timeline.extract(range(96, 120), &[video_id]), Err(TimelineError::TrackLocked(_))));
+        assert_eq!(timeline, before);
+    }
+
+    /// A subtitle track with captions at 0-48, 72-120 and 144-192.
+    fn subtitle_track() -> Track {
+        let mut track = Track::new("Subtitles", TrackKind::Subtitle);
+        for (text, start, end) in [("one", 0, 48), ("two", 72, 120), ("three", 144, 192)] {
+            track.add_caption(Caption::new(text, range(start, end))).unwrap();
+        }
+        track
+    }
+
+    fn caption_ranges(track: &Track) -> Vec<(String, TimeRange)> {
+        track.captions().iter().map(|c| (c.text.clone(), c.range)).collect()
+    }
+
+    #[test]
+    fn insert_gap_pushes_and_splits_captions() {
+        let mut track = subtitle_track();
+        track.insert_gap(range(96, 120)).unwrap();
+        assert_eq!(
+            caption_ranges(&track),
+            vec![
+                ("one".into(), range(0, 48)),
+                ("two".into(), range(72, 96)),
+                ("two".into(), range(120, 144)),
+                ("three".into(), range(168, 216)),
+            ]
+        );
+        assert_ne!(track.captions()[1].id, track.captions()[2].id);
+    }
+
+    #[test]
+    fn lift_and_extract_trim_captions() {
+        let mut lifted = subtitle_track();
+        lifted.lift_range(range(24, 96)).unwrap();
+        assert_eq!(
+            caption_ranges(&lifted),
+            vec![("one".into(), range(0, 24)), ("two".into(), range(96, 120)), ("three".into(), range(144, 192))]
+        );
+
+        let mut extracted = subtitle_track();
+        extracted.extract_range(range(24, 96)).unwrap();
+        assert_eq!(
+            caption_ranges(&extracted),
+            vec![("one".into(), range(0, 24)), ("two".into(), range(24, 48)), ("three".into(), range(72, 120))]
+        );
+    }
+
+    #[test]
+    fn ripple_trim_carries_subtitle_tracks() {
+        let (mut timeline, video_id, _) = linked_timeline();
+        let subtitles = subtitle_track();
+        let subtitle_id = subtitles.id;
+        timeline.add_track(subtitles);
+        let mut locked = subtitle_track();
+        locked.locked = true;
+        let locked_id = locked.id;
+        timeline.add_track(locked);
+        let clip_id = timeline.track(video_id).unwrap().clips()[0].id;
+
+        timeline.ripple_trim(video_id, clip_id, TrimEdge::End, frames(-24)).unwrap();
+        let track = timeline.track(subtitle_id).unwrap();
+        assert_eq!(
+            caption_ranges(track),
+            vec![("one".into(), range(0, 48)), ("two".into(), range(72, 120)), ("three".into(), range(120, 168))]
+        );
+        assert_eq!(caption_ranges(timeline.track(locked_id).unwrap()), caption_ranges(&subtitle_track()));
+
+        timeline.ripple_trim(video_id, clip_id, TrimEdge::End, frames(48)).unwrap();
+        let track = timeline.track(subtitle_id).unwrap();
+        assert_eq!(caption_ranges(track)[2], ("three".into(), range(168, 216)));
+    }
+
+    #[test]
+    fn insert_and_extract_edits_carry_subtitle_tracks() {
+        let (mut timeline, video_id, _) = linked_timeline();
+        let subtitles = subtitle_track();
+        let subtitle_id = subtitles.id;
+        timeline.add_track(subtitles);
+
+        let points = EditPoints::new().with_source(frames(0), frames(24)).with_record(frames(0), frames(24));
+        timeline.insert_edit(MediaSource::new("insert.mov"), points, &[video_id]).unwrap();
+        assert_eq!(
+            caption_ranges(timeline.track(subtitle_id).unwrap()),
+            vec![("one".into(), range(24, 72)), ("two".into(), range(96, 144)), ("three".into(), range(168, 216))]
+        );
+
+        let clipboard = timeline.extract(range(48, 120), &[video_id]).unwrap();
+        assert_eq!(
+            caption_ranges(timeline.track(subtitle_id).unwrap()),
+            vec![("one".into(), range(24, 48)), ("two".into(), range(48, 72)), ("three".into(), range(96, 144))]
+        );
+        let copied = clipboard.tracks.iter().find(|t| t.kind == TrackKind::Subtitle).unwrap();
+        assert_eq!(caption_ranges(copied), vec![("one".into(), range(0, 24)), ("two".into(), range(48, 72))]);
+    }
+}
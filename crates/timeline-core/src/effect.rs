@@ -0,0 +1,552 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use uuid::Uuid;
+use crate::{RationalTime, TimeRange};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn black() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParameterValue {
+    Boolean(bool),
+    Number(f64),
+    /// Both free text and `enum` choices.
+    Text(String),
+    Color(Color),
+    Point(Point),
+}
+
+impl ParameterValue {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ParameterValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ParameterValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Blends towards `other` by `t`. Values without a meaningful blend,
+    /// such as text or mismatched kinds, hold until `t` reaches 1.
+    fn lerp(&self, other: &ParameterValue, t: f64) -> ParameterValue {
+        let mix = |a: f64, b: f64| a + (b - a) * t;
+        match (self, other) {
+            (ParameterValue::Number(a), ParameterValue::Number(b)) => ParameterValue::Number(mix(*a, *b)),
+            (ParameterValue::Color(a), ParameterValue::Color(b)) => ParameterValue::Color(Color::new(
+                mix(a.r, b.r),
+                mix(a.g, b.g),
+                mix(a.b, b.b),
+                mix(a.a, b.a),
+            )),
+            (ParameterValue::Point(a), ParameterValue::Point(b)) => {
+                ParameterValue::Point(Point::new(mix(a.x, b.x), mix(a.y, b.y)))
+            }
+            _ if t >= 1.0 => other.clone(),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl From<f64> for ParameterValue {
+    fn from(value: f64) -> Self {
+        ParameterValue::Number(value)
+    }
+}
+
+impl From<bool> for ParameterValue {
+    fn from(value: bool) -> Self {
+        ParameterValue::Boolean(value)
+    }
+}
+
+impl From<Color> for ParameterValue {
+    fn from(value: Color) -> Self {
+        ParameterValue::Color(value)
+    }
+}
+
+impl From<Point> for ParameterValue {
+    fn from(value: Point) -> Self {
+        ParameterValue::Point(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterType {
+    Number,
+    String,
+    Boolean,
+    Color,
+    Point,
+    Enum,
+}
+
+/// How a keyframe blends into the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Cubic bezier shaped by the keyframes' tangents.
+    Bezier,
+    /// Jumps to the next value halfway between the keyframes.
+    Step,
+    /// Keeps this keyframe's value until the next keyframe.
+    Hold,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Keyframe {
+    /// Offset from the start of whatever owns the parameter, e.g. the clip.
+    pub time: RationalTime,
+    pub value: ParameterValue,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Control point leaving this keyframe for a bezier segment, as a
+    /// fraction of the segment's duration and value change. Defaults to
+    /// `(1/3, 1/3)`, which with the default in tangent is a straight line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out_tangent: Option<Point>,
+    /// Control point arriving at this keyframe, defaulting to `(2/3, 2/3)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_tangent: Option<Point>,
+}
+
+impl Keyframe {
+    pub fn new(time: RationalTime, value: impl Into<ParameterValue>, interpolation: Interpolation) -> Self {
+        Self {
+            time,
+            value: value.into(),
+            interpolation,
+            out_tangent: None,
+            in_tangent: None,
+        }
+    }
+
+    pub fn with_tangents(mut self, in_tangent: Point, out_tangent: Point) -> Self {
+        self.in_tangent = Some(in_tangent);
+        self.out_tangent = Some(out_tangent);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ParameterType,
+    /// The value used when there are no keyframes.
+    pub value: ParameterValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<String>>,
+    /// Ordered by time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "deserialize_keyframes")]
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl EffectParameter {
+    pub fn new(name: impl Into<String>, kind: ParameterType, value: impl Into<ParameterValue>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            value: value.into(),
+            min_value: None,
+            max_value: None,
+            enum_values: None,
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn number(name: impl Into<String>, value: f64) -> Self {
+        Self::new(name, ParameterType::Number, value)
+    }
+
+    pub fn choice(name: impl Into<String>, value: impl Into<String>, options: Vec<String>) -> Self {
+        Self {
+            enum_values: Some(options),
+            ..Self::new(name, ParameterType::Enum, ParameterValue::Text(value.into()))
+        }
+    }
+
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.min_value = Some(min);
+        self.max_value = Some(max);
+        self
+    }
+
+    /// Adds a keyframe, replacing any existing one at the same time.
+    pub fn set_keyframe(&mut self, keyframe: Keyframe) {
+        set_keyframe(&mut self.keyframes, keyframe);
+    }
+
+    pub fn remove_keyframe(&mut self, time: &RationalTime) -> Option<Keyframe> {
+        remove_keyframe(&mut self.keyframes, time)
+    }
+
+    pub fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty()
+    }
+
+    /// The parameter's value at `time`, measured like the keyframe times.
+    /// Before the first and after the last keyframe the value holds, and
+    /// numbers are clamped to `min_value..=max_value`.
+    pub fn value_at(&self, time: &RationalTime) -> ParameterValue {
+        let value = evaluate_keyframes(&self.keyframes, time).unwrap_or_else(|| self.value.clone());
+        match value {
+            ParameterValue::Number(n) => ParameterValue::Number(self.clamp(n)),
+            other => other,
+        }
+    }
+
+    pub fn number_at(&self, time: &RationalTime) -> Option<f64> {
+        self.value_at(time).as_f64()
+    }
+
+    fn clamp(&self, value: f64) -> f64 {
+        let value = self.min_value.map_or(value, |min| value.max(min));
+        self.max_value.map_or(value, |max| value.min(max))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EffectType {
+    Filter,
+    Transition,
+    Generator,
+    Color,
+    Audio,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Effect {
+    pub id: Uuid,
+    pub name: String,
+    pub effect_type: EffectType,
+    pub enabled: bool,
+    pub parameters: Vec<EffectParameter>,
+    /// Limits the effect to part of its owner, relative to the owner's start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_range: Option<TimeRange>,
+}
+
+impl Effect {
+    pub fn new(name: impl Into<String>, effect_type: EffectType) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            effect_type,
+            enabled: true,
+            parameters: Vec::new(),
+            time_range: None,
+        }
+    }
+
+    pub fn with_parameter(mut self, parameter: EffectParameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    pub fn with_time_range(mut self, range: TimeRange) -> Self {
+        self.time_range = Some(range);
+        self
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&EffectParameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    pub fn parameter_mut(&mut self, name: &str) -> Option<&mut EffectParameter> {
+        self.parameters.iter_mut().find(|p| p.name == name)
+    }
+
+    /// Whether the effect renders at `time`, measured from its owner's start.
+    pub fn is_active_at(&self, time: &RationalTime) -> bool {
+        self.enabled && self.time_range.as_ref().is_none_or(|range| range.contains(time))
+    }
+
+    pub fn value_at(&self, name: &str, time: &RationalTime) -> Option<ParameterValue> {
+        self.parameter(name).map(|p| p.value_at(time))
+    }
+}
+
+/// Clips saved before effects were typed list them by name alone.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EffectRepr {
+    Name(String),
+    Effect(Effect),
+}
+
+/// Reads a clip's effects, loading bare names as enabled filters with no
+/// parameters.
+pub(crate) fn deserialize_effects<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Effect>, D::Error> {
+    let effects = Vec::<EffectRepr>::deserialize(deserializer)?;
+    Ok(effects
+        .into_iter()
+        .map(|effect| match effect {
+            EffectRepr::Name(name) => Effect::new(name, EffectType::Filter),
+            EffectRepr::Effect(effect) => effect,
+        })
+        .collect())
+}
+
+/// Reads keyframes in whatever order they were saved, sorted by time.
+fn deserialize_keyframes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Keyframe>, D::Error> {
+    let mut keyframes = Vec::<Keyframe>::deserialize(deserializer)?;
+    keyframes.sort_by_key(|k| k.time);
+    Ok(keyframes)
+}
+
+pub(crate) fn set_keyframe(keyframes: &mut Vec<Keyframe>, keyframe: Keyframe) {
+    match keyframes.binary_search_by(|k| k.time.cmp(&keyframe.time)) {
+        Ok(index) => keyframes[index] = keyframe,
+        Err(index) => keyframes.insert(index, keyframe),
+    }
+}
+
+pub(crate) fn remove_keyframe(keyframes: &mut Vec<Keyframe>, time: &RationalTime) -> Option<Keyframe> {
+    let index = keyframes.binary_search_by(|k| k.time.cmp(time)).ok()?;
+    Some(keyframes.remove(index))
+}
+
+/// Interpolates sorted keyframes at `time`, or `None` if there are none.
+pub fn evaluate_keyframes(keyframes: &[Keyframe], time: &RationalTime) -> Option<ParameterValue> {
+    let next = keyframes.partition_point(|k| k.time <= *time);
+    let Some(index) = next.checked_sub(1) else {
+        return keyframes.first().map(|k| k.value.clone());
+    };
+    let from = &keyframes[index];
+    let Some(to) = keyframes.get(next) else {
+        return Some(from.value.clone());
+    };
+
+    let t = segment_fraction(&from.time, &to.time, time);
+    let eased = match from.interpolation {
+        Interpolation::Linear => t,
+        Interpolation::Hold => 0.0,
+        Interpolation::Step if t < 0.5 => 0.0,
+        Interpolation::Step => 1.0,
+        Interpolation::Bezier => {
+            let out = from.out_tangent.unwrap_or(Point::new(1.0 / 3.0, 1.0 / 3.0));
+            let into = to.in_tangent.unwrap_or(Point::new(2.0 / 3.0, 2.0 / 3.0));
+            cubic_bezier(out, into, t)
+        }
+    };
+    Some(from.value.lerp(&to.value, eased))
+}
+
+/// How far `time` is between `start` and `end`, computed from exact frame
+/// counts so every platform gets the same fraction. Times whose rates
+/// share no exact base fall back to seconds.
+fn segment_fraction(start: &RationalTime, end: &RationalTime, time: &RationalTime) -> f64 {
+    let (Ok(length), Ok(elapsed)) = (end.checked_sub(start), time.checked_sub(start)) else {
+        return (time.to_seconds() - start.to_seconds()) / (end.to_seconds() - start.to_seconds());
+    };
+    if length.value == 0 {
+        return 1.0;
+    }
+    elapsed.ratio_to(&length)
+}
+
+/// Evaluates the unit cubic bezier `(0,0) p1 p2 (1,1)` at `x`, solving for
+/// the curve parameter with Newton's method and falling back to bisection.
+fn cubic_bezier(p1: Point, p2: Point, x: f64) -> f64 {
+    let x1 = p1.x.clamp(0.0, 1.0);
+    let x2 = p2.x.clamp(0.0, 1.0);
+    let coord = |a: f64, b: f64, s: f64| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * a + 3.0 * inv * s * s * b + s * s * s
+    };
+    let slope = |a: f64, b: f64, s: f64| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * a + 6.0 * inv * s * (b - a) + 3.0 * s * s * (1.0 - b)
+    };
+
+    let mut s = x;
+    for _ in 0..8 {
+        let error = coord(x1, x2, s) - x;
+        if error.abs() < 1e-9 {
+            return coord(p1.y, p2.y, s);
+        }
+        let d = slope(x1, x2, s);
+        if d.abs() < 1e-9 {
+            break;
+        }
+        s = (s - error / d).clamp(0.0, 1.0);
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    s = x;
+    for _ in 0..64 {
+        if coord(x1, x2, s) < x {
+            low = s;
+        } else {
+            high = s;
+        }
+        s = (low + high) / 2.0;
+    }
+    coord(p1.y, p2.y, s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::Clip;
+
+    #[test]
+    fn legacy_effect_names_load_as_filters() {
+        let range = TimeRange::new(RationalTime::new(0, 24), RationalTime::new(48, 24));
+        let tint = Effect::new("tint", EffectType::Color);
+        let mut saved = serde_json::to_value(Clip::new("shot", "shot.mov", range, range)).unwrap();
+        saved["effects"] = json!(["blur", tint]);
+
+        let clip: Clip = serde_json::from_value(saved).unwrap();
+        assert_eq!(clip.effects.len(), 2);
+        assert_eq!(clip.effects[0].name, "blur");
+        assert_eq!(clip.effects[0].effect_type, EffectType::Filter);
+        assert!(clip.effects[0].enabled && clip.effects[0].parameters.is_empty());
+        assert_eq!(clip.effects[1], tint);
+    }
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    /// 0 at frame 24 and 10 at frame 48, leaving the first keyframe with
+    /// `interpolation`.
+    fn ramp(interpolation: Interpolation) -> Vec<Keyframe> {
+        vec![
+            Keyframe::new(frames(24), 0.0, interpolation),
+            Keyframe::new(frames(48), 10.0, Interpolation::Linear),
+        ]
+    }
+
+    fn number(keyframes: &[Keyframe], frame: i64) -> f64 {
+        evaluate_keyframes(keyframes, &frames(frame)).unwrap().as_f64().unwrap()
+    }
+
+    #[test]
+    fn values_hold_outside_the_keyframes() {
+        assert_eq!(evaluate_keyframes(&[], &frames(0)), None);
+        for interpolation in [Interpolation::Linear, Interpolation::Bezier, Interpolation::Step, Interpolation::Hold] {
+            let keyframes = ramp(interpolation);
+            assert_eq!(number(&keyframes, 0), 0.0);
+            assert_eq!(number(&keyframes, 24), 0.0);
+            assert_eq!(number(&keyframes, 48), 10.0);
+            assert_eq!(number(&keyframes, 100), 10.0);
+        }
+    }
+
+    #[test]
+    fn linear_step_and_hold_segments() {
+        let linear = ramp(Interpolation::Linear);
+        assert_eq!(number(&linear, 30), 2.5);
+        assert_eq!(number(&linear, 36), 5.0);
+
+        let step = ramp(Interpolation::Step);
+        assert_eq!(number(&step, 35), 0.0);
+        assert_eq!(number(&step, 36), 10.0);
+
+        let hold = ramp(Interpolation::Hold);
+        assert_eq!(number(&hold, 47), 0.0);
+
+        // Colours and points blend per channel; text jumps at the next key.
+        let colors = [
+            Keyframe::new(frames(0), Color::new(0.0, 0.0, 0.0, 1.0), Interpolation::Linear),
+            Keyframe::new(frames(24), Color::new(1.0, 0.5, 0.0, 1.0), Interpolation::Linear),
+        ];
+        assert_eq!(evaluate_keyframes(&colors, &frames(12)), Some(ParameterValue::Color(Color::new(0.5, 0.25, 0.0, 1.0))));
+        let text = [
+            Keyframe::new(frames(0), ParameterValue::Text("a".into()), Interpolation::Linear),
+            Keyframe::new(frames(24), ParameterValue::Text("b".into()), Interpolation::Linear),
+        ];
+        assert_eq!(evaluate_keyframes(&text, &frames(23)), Some(ParameterValue::Text("a".into())));
+    }
+
+    #[test]
+    fn bezier_segments_follow_their_tangents() {
+        // The default tangents make a straight line.
+        let straight = ramp(Interpolation::Bezier);
+        assert!((number(&straight, 30) - 2.5).abs() < 1e-6);
+
+        // CSS `ease-in-out` is symmetric about the middle, slow at the ends.
+        let mut eased = ramp(Interpolation::Bezier);
+        eased[0].out_tangent = Some(Point::new(0.42, 0.0));
+        eased[1].in_tangent = Some(Point::new(0.58, 1.0));
+        assert!((number(&eased, 36) - 5.0).abs() < 1e-6);
+        assert!(number(&eased, 30) < 2.5);
+        assert!((number(&eased, 30) + number(&eased, 42) - 10.0).abs() < 1e-6);
+
+        // Tangents that flatten the curve's x in the middle stall Newton's
+        // method there; the bisection fallback must still land on the curve.
+        let curve = |s: f64, a: f64, b: f64| 3.0 * (1.0 - s).powi(2) * s * a + 3.0 * (1.0 - s) * s * s * b + s.powi(3);
+        for step in 1..24 {
+            let x = step as f64 / 24.0;
+            let y = cubic_bezier(Point::new(1.0, 0.0), Point::new(0.0, 1.0), x);
+            let s = (0..=100_000).map(|i| i as f64 / 100_000.0).min_by(|a, b| {
+                (curve(*a, 1.0, 0.0) - x).abs().total_cmp(&(curve(*b, 1.0, 0.0) - x).abs())
+            });
+            assert!((y - curve(s.unwrap(), 0.0, 1.0)).abs() < 1e-3, "x = {x}");
+        }
+    }
+
+    #[test]
+    fn keyframes_load_sorted() {
+        let parameter = json!({
+            "name": "opacity",
+            "type": "number",
+            "value": 1.0,
+            "keyframes": [
+                { "time": frames(48), "value": 1.0 },
+                { "time": frames(0), "value": 0.0 },
+                { "time": frames(24), "value": 0.5 },
+            ],
+        });
+        let parameter: EffectParameter = serde_json::from_value(parameter).unwrap();
+        let times: Vec<_> = parameter.keyframes.iter().map(|k| k.time).collect();
+        assert_eq!(times, vec![frames(0), frames(24), frames(48)]);
+        assert_eq!(parameter.number_at(&frames(12)), Some(0.25));
+    }
+}
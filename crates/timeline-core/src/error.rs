@@ -1,4 +1,5 @@
 use thiserror::Error;
+use crate::FrameRate;
 
 #[derive(Debug, Clone, Error)]
 pub enum TimelineError {
@@ -8,6 +9,18 @@ pub enum TimelineError {
     #[error("Track not found: {0}")]
     TrackNotFound(uuid::Uuid),
 
+    #[error("Clip is locked: {0}")]
+    ClipLocked(uuid::Uuid),
+
+    #[error("Track is locked: {0}")]
+    TrackLocked(uuid::Uuid),
+
+    #[error("Not enough source media for clip {0}")]
+    InsufficientMedia(uuid::Uuid),
+
+    #[error("Clip {clip_id} needs {required} frames of handle media but only has {available}")]
+    InsufficientHandles { clip_id: uuid::Uuid, required: i64, available: i64 },
+
     #[error("Invalid time range: start {start} exceeds end {end}")]
     InvalidTimeRange { start: i64, end: i64 },
 
@@ -15,7 +28,28 @@ pub enum TimelineError {
     ClipOverlap { time: f64 },
 
     #[error("Invalid frame rate: {0}")]
-    InvalidFrameRate(u32),
+    InvalidFrameRate(FrameRate),
+
+    #[error("No frame rate holds both {0} and {1} exactly")]
+    IncompatibleRates(FrameRate, FrameRate),
+
+    #[error("Invalid timecode: {0}")]
+    InvalidTimecode(String),
+
+    #[error("Invalid chapters: {0}")]
+    InvalidChapters(String),
+
+    #[error("Timeline not found: {0}")]
+    TimelineNotFound(uuid::Uuid),
+
+    #[error("Timeline {0} would contain itself through compound clips")]
+    CircularReference(uuid::Uuid),
+
+    #[error("Multicam source not found: {0}")]
+    MulticamNotFound(uuid::Uuid),
+
+    #[error("Multicam angle not found: {0}")]
+    AngleNotFound(usize),
 
     #[error("Media source not found: {0}")]
     MediaSourceNotFound(String),
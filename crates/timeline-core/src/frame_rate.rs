@@ -0,0 +1,201 @@
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::TimelineError;
+
+/// A frame rate expressed as an exact fraction, e.g. `30000/1001` for 29.97.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl FrameRate {
+    pub const FPS_23_976: Self = Self { numerator: 24000, denominator: 1001 };
+    pub const FPS_24: Self = Self { numerator: 24, denominator: 1 };
+    pub const FPS_25: Self = Self { numerator: 25, denominator: 1 };
+    pub const FPS_29_97: Self = Self { numerator: 30000, denominator: 1001 };
+    pub const FPS_30: Self = Self { numerator: 30, denominator: 1 };
+    pub const FPS_48: Self = Self { numerator: 48, denominator: 1 };
+    pub const FPS_50: Self = Self { numerator: 50, denominator: 1 };
+    pub const FPS_59_94: Self = Self { numerator: 60000, denominator: 1001 };
+    pub const FPS_60: Self = Self { numerator: 60, denominator: 1 };
+
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        let divisor = gcd(numerator, denominator).max(1);
+        Self {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    pub fn try_new(numerator: u32, denominator: u32) -> Result<Self, TimelineError> {
+        let rate = Self::new(numerator, denominator);
+        rate.validate()?;
+        Ok(rate)
+    }
+
+    /// Maps a decimal rate such as `29.97` onto the matching integer or
+    /// NTSC (`n * 1000 / 1001`) rate.
+    pub fn from_f64(fps: f64) -> Option<Self> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return None;
+        }
+        if (fps - fps.round()).abs() < 1e-6 {
+            return Some(Self::new(fps.round() as u32, 1));
+        }
+        let nominal = (fps * 1.001).round();
+        if (fps - nominal * 1000.0 / 1001.0).abs() < 0.01 {
+            return Some(Self::new(nominal as u32 * 1000, 1001));
+        }
+        None
+    }
+
+    pub fn validate(&self) -> Result<(), TimelineError> {
+        if self.numerator == 0 || self.denominator == 0 {
+            return Err(TimelineError::InvalidFrameRate(*self));
+        }
+        Ok(())
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The whole number of frames labelled per second, e.g. 30 for 29.97.
+    pub fn nominal(&self) -> u32 {
+        (self.numerator + self.denominator / 2) / self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn is_ntsc(&self) -> bool {
+        self.denominator == 1001
+    }
+}
+
+impl Default for FrameRate {
+    fn default() -> Self {
+        Self::FPS_24
+    }
+}
+
+impl From<u32> for FrameRate {
+    fn from(fps: u32) -> Self {
+        Self::new(fps, 1)
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fraction {
+    numerator: u32,
+    denominator: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameRateRepr {
+    Integer(u32),
+    Decimal(f64),
+    Fraction(Fraction),
+}
+
+impl Serialize for FrameRate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_integer() {
+            serializer.serialize_u32(self.numerator)
+        } else {
+            Fraction {
+                numerator: self.numerator,
+                denominator: self.denominator,
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        match FrameRateRepr::deserialize(deserializer)? {
+            FrameRateRepr::Integer(fps) => Self::try_new(fps, 1).map_err(D::Error::custom),
+            FrameRateRepr::Decimal(fps) => Self::from_f64(fps)
+                .ok_or_else(|| D::Error::custom(format!("unsupported frame rate {}", fps))),
+            FrameRateRepr::Fraction(Fraction { numerator, denominator }) => {
+                Self::try_new(numerator, denominator).map_err(D::Error::custom)
+            }
+        }
+    }
+}
+
+fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(value: serde_json::Value) -> Result<FrameRate, serde_json::Error> {
+        serde_json::from_value(value)
+    }
+
+    #[test]
+    fn integers_decimals_and_fractions_load() {
+        assert_eq!(load(json!(24)).unwrap(), FrameRate::FPS_24);
+        assert_eq!(load(json!(25.0)).unwrap(), FrameRate::FPS_25);
+        assert_eq!(load(json!(29.97)).unwrap(), FrameRate::FPS_29_97);
+        assert_eq!(load(json!(23.976)).unwrap(), FrameRate::FPS_23_976);
+        assert_eq!(load(json!(59.94)).unwrap(), FrameRate::FPS_59_94);
+        assert_eq!(load(json!({ "numerator": 30000, "denominator": 1001 })).unwrap(), FrameRate::FPS_29_97);
+        // Fractions are reduced.
+        assert_eq!(load(json!({ "numerator": 48, "denominator": 2 })).unwrap(), FrameRate::FPS_24);
+    }
+
+    #[test]
+    fn zero_negative_and_odd_rates_are_rejected() {
+        for value in [
+            json!(0),
+            json!(0.0),
+            json!(-24),
+            json!(-29.97),
+            json!(12.345),
+            json!({ "numerator": 0, "denominator": 1 }),
+            json!({ "numerator": 24, "denominator": 0 }),
+            json!({ "numerator": -24, "denominator": 1 }),
+            json!("24"),
+        ] {
+            assert!(load(value.clone()).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn integer_rates_save_as_numbers() {
+        assert_eq!(serde_json::to_value(FrameRate::FPS_24).unwrap(), json!(24));
+        assert_eq!(
+            serde_json::to_value(FrameRate::FPS_29_97).unwrap(),
+            json!({ "numerator": 30000, "denominator": 1001 })
+        );
+        for rate in [FrameRate::FPS_23_976, FrameRate::FPS_25, FrameRate::FPS_59_94] {
+            assert_eq!(load(serde_json::to_value(rate).unwrap()).unwrap(), rate);
+        }
+    }
+}
@@ -0,0 +1,586 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{Clip, Marker, MulticamSource, Timeline, TimelineError, Track};
+
+/// A reversible change to a timeline. Commands carry full before/after
+/// values so they can be replayed or inverted without the original context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EditCommand {
+    /// Changes the timeline's name, metadata and start time; its tracks,
+    /// markers and multicams are left alone.
+    UpdateTimeline { before: Box<Timeline>, after: Box<Timeline> },
+    /// Puts the listed tracks in the listed order, using the positions they
+    /// already hold between them. Other tracks stay where they are.
+    ReorderTracks { before: Vec<Uuid>, after: Vec<Uuid> },
+    AddTrack { index: usize, track: Box<Track> },
+    RemoveTrack { index: usize, track: Box<Track> },
+    /// Changes a track's own settings; its clips are left alone.
+    UpdateTrack { before: Box<Track>, after: Box<Track> },
+    AddClip { track_id: Uuid, clip: Box<Clip> },
+    RemoveClip { track_id: Uuid, clip: Box<Clip> },
+    UpdateClip { track_id: Uuid, before: Box<Clip>, after: Box<Clip> },
+    /// Timeline-level markers; track and clip markers travel with their owner.
+    AddMarker { marker: Box<Marker> },
+    RemoveMarker { marker: Box<Marker> },
+    UpdateMarker { before: Box<Marker>, after: Box<Marker> },
+    AddMulticam { multicam: Box<MulticamSource> },
+    RemoveMulticam { multicam: Box<MulticamSource> },
+    UpdateMulticam { before: Box<MulticamSource>, after: Box<MulticamSource> },
+}
+
+impl EditCommand {
+    pub fn apply(&self, timeline: &mut Timeline) -> Result<(), TimelineError> {
+        match self {
+            EditCommand::UpdateTimeline { after, .. } => {
+                timeline.name = after.name.clone();
+                timeline.metadata = after.metadata.clone();
+                timeline.global_start_time = after.global_start_time;
+            }
+            EditCommand::ReorderTracks { after, .. } => {
+                let listed: HashSet<Uuid> = after.iter().copied().collect();
+                let slots: Vec<usize> = (0..timeline.tracks.len()).filter(|&i| listed.contains(&timeline.tracks[i].id)).collect();
+                if slots.len() != after.len() {
+                    return Err(TimelineError::InvalidState("reordered tracks do not match the timeline".into()));
+                }
+                let mut moved: HashMap<Uuid, Track> = slots.iter().map(|&i| (timeline.tracks[i].id, timeline.tracks[i].clone())).collect();
+                for (&slot, id) in slots.iter().zip(after) {
+                    timeline.tracks[slot] = moved.remove(id).ok_or(TimelineError::TrackNotFound(*id))?;
+                }
+            }
+            EditCommand::AddTrack { index, track } => {
+                let index = (*index).min(timeline.tracks.len());
+                timeline.tracks.insert(index, (**track).clone());
+            }
+            EditCommand::RemoveTrack { track, .. } => {
+                timeline
+                    .remove_track(track.id)
+                    .ok_or(TimelineError::TrackNotFound(track.id))?;
+            }
+            EditCommand::UpdateTrack { after, .. } => {
+                let track = track_mut(timeline, after.id)?;
+                let clips = std::mem::take(&mut track.clips);
+                *track = (**after).clone();
+                track.clips = clips;
+            }
+            EditCommand::AddClip { track_id, clip } => {
+                track_mut(timeline, *track_id)?.clips.push((**clip).clone());
+            }
+            EditCommand::RemoveClip { track_id, clip } => {
+                track_mut(timeline, *track_id)?
+                    .remove_clip(clip.id)
+                    .ok_or(TimelineError::ClipNotFound(clip.id))?;
+            }
+            EditCommand::UpdateClip { track_id, after, .. } => {
+                let existing = track_mut(timeline, *track_id)?
+                    .clips
+                    .iter_mut()
+                    .find(|c| c.id == after.id)
+                    .ok_or(TimelineError::ClipNotFound(after.id))?;
+                *existing = (**after).clone();
+            }
+            EditCommand::AddMarker { marker } => timeline.add_marker((**marker).clone()),
+            EditCommand::RemoveMarker { marker } => {
+                timeline
+                    .remove_marker(marker.id)
+                    .ok_or_else(|| TimelineError::InvalidState(format!("marker {} not found", marker.id)))?;
+            }
+            EditCommand::UpdateMarker { before, after } => {
+                timeline
+                    .remove_marker(before.id)
+                    .ok_or_else(|| TimelineError::InvalidState(format!("marker {} not found", before.id)))?;
+                timeline.add_marker((**after).clone());
+            }
+            // Clips playing the source are brought up to date by their own
+            // commands.
+            EditCommand::AddMulticam { multicam } | EditCommand::UpdateMulticam { after: multicam, .. } => {
+                timeline.multicams.insert(multicam.id, (**multicam).clone());
+            }
+            EditCommand::RemoveMulticam { multicam } => {
+                timeline
+                    .multicams
+                    .remove(&multicam.id)
+                    .ok_or(TimelineError::MulticamNotFound(multicam.id))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn inverted(&self) -> EditCommand {
+        match self.clone() {
+            EditCommand::UpdateTimeline { before, after } => EditCommand::UpdateTimeline { before: after, after: before },
+            EditCommand::ReorderTracks { before, after } => EditCommand::ReorderTracks { before: after, after: before },
+            EditCommand::AddTrack { index, track } => EditCommand::RemoveTrack { index, track },
+            EditCommand::RemoveTrack { index, track } => EditCommand::AddTrack { index, track },
+            EditCommand::UpdateTrack { before, after } => EditCommand::UpdateTrack { before: after, after: before },
+            EditCommand::AddClip { track_id, clip } => EditCommand::RemoveClip { track_id, clip },
+            EditCommand::RemoveClip { track_id, clip } => EditCommand::AddClip { track_id, clip },
+            EditCommand::UpdateClip { track_id, before, after } => {
+                EditCommand::UpdateClip { track_id, before: after, after: before }
+            }
+            EditCommand::AddMarker { marker } => EditCommand::RemoveMarker { marker },
+            EditCommand::RemoveMarker { marker } => EditCommand::AddMarker { marker },
+            EditCommand::UpdateMarker { before, after } => EditCommand::UpdateMarker { before: after, after: before },
+            EditCommand::AddMulticam { multicam } => EditCommand::RemoveMulticam { multicam },
+            EditCommand::RemoveMulticam { multicam } => EditCommand::AddMulticam { multicam },
+            EditCommand::UpdateMulticam { before, after } => EditCommand::UpdateMulticam { before: after, after: before },
+        }
+    }
+
+    /// The commands that turn `before` into `after`, matching tracks and
+    /// clips by id.
+    pub fn diff(before: &Timeline, after: &Timeline) -> Vec<EditCommand> {
+        let mut commands = Vec::new();
+
+        if timeline_settings(before) != timeline_settings(after) {
+            commands.push(EditCommand::UpdateTimeline {
+                before: Box::new(timeline_settings(before)),
+                after: Box::new(timeline_settings(after)),
+            });
+        }
+
+        // Removed from the back so that undo re-inserts them front to back,
+        // each at an index that already accounts for the ones before it.
+        for (index, old) in before.tracks.iter().enumerate().rev() {
+            if after.track(old.id).is_none() {
+                commands.push(EditCommand::RemoveTrack { index, track: Box::new(old.clone()) });
+            }
+        }
+
+        for old in &before.tracks {
+            let Some(new) = after.track(old.id) else { continue };
+            if track_settings(old) != track_settings(new) {
+                commands.push(EditCommand::UpdateTrack {
+                    before: Box::new(track_settings(old)),
+                    after: Box::new(track_settings(new)),
+                });
+            }
+            let new_clips: HashMap<Uuid, &Clip> = new.clips.iter().map(|c| (c.id, c)).collect();
+            let old_ids: HashSet<Uuid> = old.clips.iter().map(|c| c.id).collect();
+            for clip in &old.clips {
+                match new_clips.get(&clip.id) {
+                    None => commands.push(EditCommand::RemoveClip { track_id: old.id, clip: Box::new(clip.clone()) }),
+                    Some(&updated) if updated != clip => commands.push(EditCommand::UpdateClip {
+                        track_id: old.id,
+                        before: Box::new(clip.clone()),
+                        after: Box::new(updated.clone()),
+                    }),
+                    Some(_) => {}
+                }
+            }
+            for clip in new.clips.iter().filter(|c| !old_ids.contains(&c.id)) {
+                commands.push(EditCommand::AddClip { track_id: old.id, clip: Box::new(clip.clone()) });
+            }
+        }
+
+        for (index, new) in after.tracks.iter().enumerate() {
+            if before.track(new.id).is_none() {
+                commands.push(EditCommand::AddTrack { index, track: Box::new(new.clone()) });
+            }
+        }
+
+        // Tracks on both sides, each in its own order.
+        let kept: Vec<Uuid> = before.tracks.iter().map(|t| t.id).filter(|id| after.track(*id).is_some()).collect();
+        let moved: Vec<Uuid> = after.tracks.iter().map(|t| t.id).filter(|id| before.track(*id).is_some()).collect();
+        if kept != moved {
+            commands.push(EditCommand::ReorderTracks { before: kept, after: moved });
+        }
+
+        for marker in before.markers() {
+            match after.markers().iter().find(|m| m.id == marker.id) {
+                None => commands.push(EditCommand::RemoveMarker { marker: Box::new(marker.clone()) }),
+                Some(updated) if updated != marker => commands.push(EditCommand::UpdateMarker {
+                    before: Box::new(marker.clone()),
+                    after: Box::new(updated.clone()),
+                }),
+                Some(_) => {}
+            }
+        }
+        for marker in after.markers().iter().filter(|m| before.markers().iter().all(|o| o.id != m.id)) {
+            commands.push(EditCommand::AddMarker { marker: Box::new(marker.clone()) });
+        }
+
+        for multicam in before.multicams() {
+            match after.multicam(multicam.id) {
+                None => commands.push(EditCommand::RemoveMulticam { multicam: Box::new(multicam.clone()) }),
+                Some(updated) if updated != multicam => commands.push(EditCommand::UpdateMulticam {
+                    before: Box::new(multicam.clone()),
+                    after: Box::new(updated.clone()),
+                }),
+                Some(_) => {}
+            }
+        }
+        for multicam in after.multicams().filter(|m| before.multicam(m.id).is_none()) {
+            commands.push(EditCommand::AddMulticam { multicam: Box::new(multicam.clone()) });
+        }
+        commands
+    }
+}
+
+/// One undoable step as shown to the user, e.g. "Ripple trim".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub label: String,
+    pub commands: Vec<EditCommand>,
+}
+
+impl HistoryEntry {
+    fn apply(&self, timeline: &mut Timeline) -> Result<(), TimelineError> {
+        apply_all(timeline, self.commands.iter().cloned())
+    }
+
+    fn revert(&self, timeline: &mut Timeline) -> Result<(), TimelineError> {
+        apply_all(timeline, self.commands.iter().rev().map(EditCommand::inverted))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    undo_stack: VecDeque<HistoryEntry>,
+    redo_stack: Vec<HistoryEntry>,
+    limit: usize,
+    open_group: Option<HistoryEntry>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            limit,
+            open_group: None,
+        }
+    }
+
+    pub fn execute(&mut self, timeline: &mut Timeline, label: impl Into<String>, command: EditCommand) -> Result<(), TimelineError> {
+        apply_all(timeline, std::iter::once(command.clone()))?;
+        self.record(HistoryEntry { label: label.into(), commands: vec![command] });
+        Ok(())
+    }
+
+    /// Runs any timeline operation and records what it changed, so existing
+    /// editing methods become undoable without their own command variants.
+    pub fn perform<T, F>(&mut self, timeline: &mut Timeline, label: impl Into<String>, operation: F) -> Result<T, TimelineError>
+    where
+        F: FnOnce(&mut Timeline) -> Result<T, TimelineError>,
+    {
+        let before = timeline.clone();
+        let output = match operation(timeline) {
+            Ok(output) => output,
+            Err(err) => {
+                *timeline = before;
+                return Err(err);
+            }
+        };
+        let commands = EditCommand::diff(&before, timeline);
+        if !commands.is_empty() {
+            self.record(HistoryEntry { label: label.into(), commands });
+        }
+        Ok(output)
+    }
+
+    /// Starts collecting every following step into one entry, for gestures
+    /// like a drag that apply many small edits.
+    pub fn begin_group(&mut self, label: impl Into<String>) {
+        self.end_group();
+        self.open_group = Some(HistoryEntry { label: label.into(), commands: Vec::new() });
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.open_group.take() {
+            if !group.commands.is_empty() {
+                self.push_undo(group);
+            }
+        }
+    }
+
+    /// Reverts the most recent entry and returns its label.
+    pub fn undo(&mut self, timeline: &mut Timeline) -> Result<Option<String>, TimelineError> {
+        self.end_group();
+        let Some(entry) = self.undo_stack.pop_back() else {
+            return Ok(None);
+        };
+        if let Err(err) = entry.revert(timeline) {
+            self.undo_stack.push_back(entry);
+            return Err(err);
+        }
+        let label = entry.label.clone();
+        self.redo_stack.push(entry);
+        Ok(Some(label))
+    }
+
+    /// Re-applies the most recently undone entry and returns its label.
+    pub fn redo(&mut self, timeline: &mut Timeline) -> Result<Option<String>, TimelineError> {
+        self.end_group();
+        let Some(entry) = self.redo_stack.pop() else {
+            return Ok(None);
+        };
+        if let Err(err) = entry.apply(timeline) {
+            self.redo_stack.push(entry);
+            return Err(err);
+        }
+        let label = entry.label.clone();
+        self.undo_stack.push_back(entry);
+        Ok(Some(label))
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty() || self.open_group.as_ref().is_some_and(|g| !g.commands.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Undoable entries, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.undo_stack.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.open_group = None;
+    }
+
+    fn record(&mut self, entry: HistoryEntry) {
+        self.redo_stack.clear();
+        match self.open_group.as_mut() {
+            Some(group) => group.commands.extend(entry.commands),
+            None => self.push_undo(entry),
+        }
+    }
+
+    fn push_undo(&mut self, entry: HistoryEntry) {
+        self.undo_stack.push_back(entry);
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+/// Applies commands as one batch: clips may pass through overlapping
+/// positions part way through, but the end state must be valid.
+fn apply_all(timeline: &mut Timeline, mut commands: impl Iterator<Item = EditCommand>) -> Result<(), TimelineError> {
+    let snapshot = timeline.clone();
+    let result = commands
+        .try_for_each(|command| command.apply(timeline))
+        .and_then(|_| timeline.tracks.iter_mut().try_for_each(Track::normalize));
+    if result.is_err() {
+        *timeline = snapshot;
+    }
+    result
+}
+
+fn track_mut(timeline: &mut Timeline, track_id: Uuid) -> Result<&mut Track, TimelineError> {
+    timeline.track_mut(track_id).ok_or(TimelineError::TrackNotFound(track_id))
+}
+
+fn timeline_settings(timeline: &Timeline) -> Timeline {
+    Timeline {
+        tracks: Vec::new(),
+        markers: Vec::new(),
+        multicams: Default::default(),
+        ..timeline.clone()
+    }
+}
+
+fn track_settings(track: &Track) -> Track {
+    Track { clips: Vec::new(), ..track.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MediaMetadata, MediaSource, RationalTime, TimeRange, TrackKind, Transition, TransitionType, TrimEdge};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// Three back-to-back clips with 48 frames of handles on both sides.
+    fn timeline() -> (Timeline, Uuid, [Uuid; 3]) {
+        let media = MediaSource::new("shot.mov").with_metadata(MediaMetadata { duration: frames(240), ..MediaMetadata::default() });
+        let mut track = Track::new("V1", TrackKind::Video);
+        let mut ids = [Uuid::nil(); 3];
+        for (index, id) in ids.iter_mut().enumerate() {
+            let start = index as i64 * 96;
+            let clip = Clip::new("shot", media.clone(), range(48, 144), range(start, start + 96));
+            *id = clip.id;
+            track.add_clip(clip).unwrap();
+        }
+        let track_id = track.id;
+        let mut timeline = Timeline::new("edit", 24);
+        timeline.add_track(track);
+        (timeline, track_id, ids)
+    }
+
+    #[test]
+    fn undo_and_redo_walk_through_mixed_edits() {
+        let (mut timeline, track_id, [a, b, _]) = timeline();
+        let mut history = History::default();
+        let mut states = vec![timeline.clone()];
+
+        history.perform(&mut timeline, "Ripple trim", |t| t.ripple_trim(track_id, a, TrimEdge::End, frames(24))).unwrap();
+        states.push(timeline.clone());
+        history
+            .perform(&mut timeline, "Add transition", |t| {
+                t.add_transition(track_id, a, b, Transition::new(TransitionType::CrossDissolve, frames(24), frames(12)))
+            })
+            .unwrap();
+        states.push(timeline.clone());
+        history.perform(&mut timeline, "Roll", |t| t.roll_edit(track_id, a, frames(-12))).unwrap();
+        states.push(timeline.clone());
+        history.perform(&mut timeline, "Extract", |t| t.extract(range(150, 200), &[track_id])).unwrap();
+        states.push(timeline.clone());
+
+        let labels: Vec<_> = history.entries().map(|e| e.label.as_str()).collect();
+        assert_eq!(labels, vec!["Ripple trim", "Add transition", "Roll", "Extract"]);
+        for (state, label) in states.iter().rev().skip(1).zip(["Extract", "Roll", "Add transition", "Ripple trim"]) {
+            assert_eq!(history.undo(&mut timeline).unwrap().as_deref(), Some(label));
+            assert_eq!(&timeline, state);
+        }
+        assert_eq!(history.undo(&mut timeline).unwrap(), None);
+        assert!(!history.can_undo());
+
+        for state in &states[1..] {
+            history.redo(&mut timeline).unwrap();
+            assert_eq!(&timeline, state);
+        }
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn grouped_steps_undo_together() {
+        let (mut timeline, track_id, [_, _, c]) = timeline();
+        let original = timeline.clone();
+        let mut history = History::default();
+
+        history.begin_group("Drag");
+        for _ in 0..5 {
+            history.perform(&mut timeline, "Move", |t| t.move_clip(track_id, c, frames(4))).unwrap();
+        }
+        assert!(history.can_undo());
+        history.end_group();
+        assert_eq!(history.entries().count(), 1);
+        assert_eq!(timeline.track(track_id).unwrap().clips()[2].timeline_range, range(212, 308));
+
+        assert_eq!(history.undo(&mut timeline).unwrap().as_deref(), Some("Drag"));
+        assert_eq!(timeline, original);
+    }
+
+    #[test]
+    fn failed_steps_change_nothing_and_new_steps_drop_redo() {
+        let (mut timeline, track_id, [a, b, _]) = timeline();
+        let mut history = History::default();
+        history.perform(&mut timeline, "Slip", |t| t.slip(track_id, b, frames(12))).unwrap();
+        let slipped = timeline.clone();
+
+        let result = history.perform(&mut timeline, "Slip", |t| t.slip(track_id, b, frames(1000)));
+        assert!(matches!(result, Err(TimelineError::InsufficientMedia(_))));
+        assert_eq!(timeline, slipped);
+        assert_eq!(history.entries().count(), 1);
+
+        history.undo(&mut timeline).unwrap();
+        assert!(history.can_redo());
+        history.perform(&mut timeline, "Roll", |t| t.roll_edit(track_id, a, frames(6))).unwrap();
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn history_is_bounded_and_serializable() {
+        let (mut timeline, track_id, [_, _, c]) = timeline();
+        let mut history = History::new(2);
+        for _ in 0..3 {
+            history.perform(&mut timeline, "Move", |t| t.move_clip(track_id, c, frames(1))).unwrap();
+        }
+        assert_eq!(history.entries().count(), 2);
+
+        let mut loaded: History = serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+        assert_eq!(loaded, history);
+        loaded.undo(&mut timeline).unwrap();
+        loaded.undo(&mut timeline).unwrap();
+        assert_eq!(timeline.track(track_id).unwrap().clips()[2].timeline_range, range(193, 289));
+    }
+
+    #[test]
+    fn timeline_settings_undo_and_redo() {
+        let (mut timeline, _, _) = timeline();
+        let original = timeline.clone();
+        let mut history = History::default();
+
+        history.perform(&mut timeline, "Rename", |t| {
+            t.name = "final".into();
+            Ok(())
+        }).unwrap();
+        history.perform(&mut timeline, "Resize", |t| {
+            t.metadata.width = 3840;
+            t.metadata.height = 2160;
+            Ok(())
+        }).unwrap();
+        history.perform(&mut timeline, "Start time", |t| {
+            t.global_start_time = RationalTime::new(86400, 24);
+            Ok(())
+        }).unwrap();
+        let edited = timeline.clone();
+        assert_eq!(history.entries().count(), 3);
+
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline.global_start_time, frames(0));
+        assert_eq!(timeline.metadata.width, 3840);
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline.metadata.width, 1920);
+        assert_eq!(timeline.name, "final");
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline, original);
+
+        for _ in 0..3 {
+            history.redo(&mut timeline).unwrap();
+        }
+        assert_eq!(timeline, edited);
+    }
+
+    #[test]
+    fn track_order_undoes_alongside_added_and_removed_tracks() {
+        let (mut timeline, v1, _) = timeline();
+        let names = |timeline: &Timeline| timeline.tracks.iter().map(|t| t.name.clone()).collect::<Vec<_>>();
+        for name in ["A1", "V2", "A2"] {
+            timeline.add_track(Track::new(name, TrackKind::Audio));
+        }
+        let original = timeline.clone();
+        let mut history = History::default();
+
+        history.perform(&mut timeline, "Reorder", |t| {
+            t.tracks.reverse();
+            Ok(())
+        }).unwrap();
+        assert_eq!(names(&timeline), ["A2", "V2", "A1", "V1"]);
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline, original);
+
+        // Drops the first and third tracks, adds one and swaps the rest.
+        history.perform(&mut timeline, "Rearrange", |t| {
+            t.remove_track(v1);
+            t.tracks.remove(1);
+            t.tracks.swap(0, 1);
+            t.tracks.insert(1, Track::new("A3", TrackKind::Audio));
+            Ok(())
+        }).unwrap();
+        let rearranged = timeline.clone();
+        assert_eq!(names(&timeline), ["A2", "A3", "A1"]);
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline, original);
+        history.redo(&mut timeline).unwrap();
+        assert_eq!(timeline, rearranged);
+    }
+}
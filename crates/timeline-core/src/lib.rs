@@ -1,19 +1,47 @@
+pub mod frame_rate;
 pub mod rational_time;
 pub mod time_range;
+pub mod timecode;
 pub mod media_source;
+pub mod effect;
+pub mod properties;
+pub mod audio;
+pub mod audio_sync;
 pub mod clip;
 pub mod track;
 pub mod transition;
 pub mod marker;
+pub mod caption;
+pub mod chapters;
+pub mod link;
+pub mod multicam;
+pub mod snap;
 pub mod timeline;
+pub mod project;
+pub mod edit;
+pub mod history;
 pub mod error;
 
-pub use rational_time::RationalTime;
+pub use frame_rate::FrameRate;
+pub use rational_time::{RationalTime, RoundingMode};
 pub use time_range::TimeRange;
-pub use media_source::{MediaSource, MediaMetadata};
+pub use timecode::{Timecode, TimecodeWrap};
+pub use media_source::{ClipSource, MediaSource, MediaMetadata, MulticamRef, SequenceSource};
+pub use effect::{Color, Effect, EffectParameter, EffectType, Interpolation, Keyframe, ParameterType, ParameterValue, Point};
+pub use properties::{Affine, AnimatedValue, BlendMode, ClipProperties, Crop, Transform};
+pub use audio::{AudioProperties, Fade, FadeCurve};
+pub use audio_sync::{find_offset, AudioSync};
 pub use clip::Clip;
-pub use track::{Track, TrackKind};
-pub use transition::{Transition, TransitionType};
-pub use marker::Marker;
+pub use track::{Gap, Stack, Track, TrackItem, TrackKind};
+pub use transition::{Direction, Easing, Transition, TransitionParameters, TransitionType};
+pub use marker::{Marker, MarkerColor, MarkerKind};
+pub use caption::{Caption, CaptionStyle, TextAlign};
+pub use chapters::{Chapter, ChapterFormat};
+pub use link::{ClipLink, SyncOffset};
+pub use multicam::{MulticamAngle, MulticamSource};
+pub use snap::{SnapContext, SnapOptions, SnapSource, SnapTarget, SnapTolerance};
 pub use timeline::{Timeline, TimelineMetadata};
+pub use project::Project;
+pub use edit::{ClipChange, ClipPlacement, EditPoints, EditResult, TrimEdge};
+pub use history::{EditCommand, History, HistoryEntry};
 pub use error::TimelineError;
@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{Clip, EditResult, RationalTime, Timeline, TimelineError, Track, TrackKind};
+
+/// Membership of a link group, typically the picture and sound of one
+/// recording. Edits made through the timeline on any member apply to all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipLink {
+    pub group_id: Uuid,
+    /// The clip's [`Clip::sync_point`] when the group was formed. Members
+    /// are in sync while they have all moved the same distance from it.
+    pub anchor: RationalTime,
+}
+
+impl ClipLink {
+    /// The link for the tail of a member cut at `at`. Tails of members of
+    /// the same group cut at the same time end up in the same new group,
+    /// so head and tail can be edited apart. The new group id is a v5 UUID
+    /// named by the cut time within the old group, so it is the same on
+    /// every platform and release.
+    pub(crate) fn split_at(&self, at: &RationalTime) -> ClipLink {
+        let mut name = Vec::with_capacity(16);
+        name.extend_from_slice(&at.value.to_be_bytes());
+        name.extend_from_slice(&at.rate.numerator.to_be_bytes());
+        name.extend_from_slice(&at.rate.denominator.to_be_bytes());
+        ClipLink {
+            group_id: Uuid::new_v5(&self.group_id, &name),
+            anchor: self.anchor,
+        }
+    }
+}
+
+/// A linked clip that has drifted against the rest of its group, shown as
+/// the red sync badge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncOffset {
+    pub group_id: Uuid,
+    pub track_id: Uuid,
+    pub clip_id: Uuid,
+    /// Timeline frames the clip's media sits late against the group's
+    /// reference clip; negative when it is early.
+    pub frames: i64,
+}
+
+impl Clip {
+    /// Where the clip's media would start on the timeline if it played from
+    /// the beginning of the file, measured at the clip's first frame.
+    pub fn sync_point(&self) -> RationalTime {
+        let start = self.timeline_range.start;
+        start - self.source_time_at(&start)
+    }
+}
+
+impl Timeline {
+    pub fn find_clip(&self, clip_id: Uuid) -> Option<(&Track, &Clip)> {
+        self.tracks
+            .iter()
+            .find_map(|track| track.clip(clip_id).map(|clip| (track, clip)))
+    }
+
+    /// Links clips on different tracks so moves, trims, splits and deletes
+    /// made through the timeline keep them together. Clips that were
+    /// already linked leave their old group.
+    pub fn link_clips(&mut self, clip_ids: &[Uuid]) -> Result<Uuid, TimelineError> {
+        if clip_ids.len() < 2 {
+            return Err(TimelineError::InvalidState("linking needs at least two clips".to_string()));
+        }
+        let mut track_ids = Vec::new();
+        for &clip_id in clip_ids {
+            let (track, _) = self.find_clip(clip_id).ok_or(TimelineError::ClipNotFound(clip_id))?;
+            track_ids.push(track.id);
+        }
+        check_one_per_track(&track_ids)?;
+
+        let group_id = Uuid::new_v4();
+        for clip in self.tracks.iter_mut().flat_map(|t| t.clips.iter_mut()) {
+            if clip_ids.contains(&clip.id) {
+                clip.link = Some(ClipLink { group_id, anchor: clip.sync_point() });
+            }
+        }
+        Ok(group_id)
+    }
+
+    /// Breaks up a link group, returning the ids of its former members.
+    pub fn unlink_clips(&mut self, group_id: Uuid) -> Vec<Uuid> {
+        let mut unlinked = Vec::new();
+        for clip in self.tracks.iter_mut().flat_map(|t| t.clips.iter_mut()) {
+            if clip.link.is_some_and(|link| link.group_id == group_id) {
+                clip.link = None;
+                unlinked.push(clip.id);
+            }
+        }
+        unlinked
+    }
+
+    /// Every member of a link group with the id of its track, in track
+    /// order.
+    pub fn linked_clips(&self, group_id: Uuid) -> Vec<(Uuid, &Clip)> {
+        self.tracks
+            .iter()
+            .flat_map(|track| track.clips.iter().map(move |clip| (track.id, clip)))
+            .filter(|(_, clip)| clip.link.is_some_and(|link| link.group_id == group_id))
+            .collect()
+    }
+
+    /// Linked clips that are out of sync with their group. Each group is
+    /// measured against its member on the highest video track, or its
+    /// first member when it has no video.
+    pub fn sync_offsets(&self) -> Vec<SyncOffset> {
+        let mut groups: Vec<Uuid> = Vec::new();
+        for link in self.tracks.iter().flat_map(|t| t.clips.iter()).filter_map(|c| c.link) {
+            if !groups.contains(&link.group_id) {
+                groups.push(link.group_id);
+            }
+        }
+
+        let rate = self.metadata.frame_rate;
+        let mut offsets = Vec::new();
+        for group_id in groups {
+            let members = self.linked_clips(group_id);
+            let reference = members
+                .iter()
+                .find(|(track_id, _)| self.track(*track_id).is_some_and(|t| t.kind == TrackKind::Video))
+                .unwrap_or(&members[0]);
+            let reference_drift = drift(reference.1);
+            for (track_id, clip) in &members {
+                let frames = (drift(clip) - reference_drift).rescaled(rate).value;
+                if frames != 0 {
+                    offsets.push(SyncOffset { group_id, track_id: *track_id, clip_id: clip.id, frames });
+                }
+            }
+        }
+        offsets
+    }
+
+    /// `track_ids` followed by every other track that holds a clip linked
+    /// to one matching `touched` on a track already in the list, so a range
+    /// edit can carry linked clips along with it.
+    pub(crate) fn linked_tracks<F>(&self, track_ids: &[Uuid], touched: F) -> Vec<Uuid>
+    where
+        F: Fn(&Clip) -> bool,
+    {
+        let mut tracks = track_ids.to_vec();
+        let mut groups = Vec::new();
+        let mut index = 0;
+        while index < tracks.len() {
+            let links = self.track(tracks[index]).into_iter().flat_map(|t| t.clips.iter()).filter(|c| touched(c)).filter_map(|c| c.link);
+            for link in links {
+                if groups.contains(&link.group_id) {
+                    continue;
+                }
+                groups.push(link.group_id);
+                for (track_id, _) in self.linked_clips(link.group_id) {
+                    if !tracks.contains(&track_id) {
+                        tracks.push(track_id);
+                    }
+                }
+            }
+            index += 1;
+        }
+        tracks
+    }
+
+    /// Runs `edit` on a clip and on every clip linked to it, each on its
+    /// own track. Nothing changes if any of them fails, so a locked member
+    /// blocks the whole edit.
+    pub(crate) fn edit_linked<F>(&mut self, track_id: Uuid, clip_id: Uuid, mut edit: F) -> Result<EditResult, TimelineError>
+    where
+        F: FnMut(&mut Track, Uuid) -> Result<EditResult, TimelineError>,
+    {
+        let clip = self
+            .track(track_id)
+            .ok_or(TimelineError::TrackNotFound(track_id))?
+            .clip(clip_id)
+            .ok_or(TimelineError::ClipNotFound(clip_id))?;
+        let mut members = vec![(track_id, clip_id)];
+        if let Some(link) = clip.link {
+            members.extend(
+                self.linked_clips(link.group_id)
+                    .into_iter()
+                    .filter(|(_, c)| c.id != clip_id)
+                    .map(|(t, c)| (t, c.id)),
+            );
+        }
+
+        let snapshot = self.tracks.clone();
+        let mut result = EditResult::default();
+        for (track_id, clip_id) in members {
+            let track = self.track_mut(track_id).ok_or(TimelineError::TrackNotFound(track_id));
+            match track.and_then(|track| edit(track, clip_id)) {
+                Ok(changes) => result.merge(changes),
+                Err(err) => {
+                    self.tracks = snapshot;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// A link group holds at most one clip per track.
+pub(crate) fn check_one_per_track(track_ids: &[Uuid]) -> Result<(), TimelineError> {
+    for (index, track_id) in track_ids.iter().enumerate() {
+        if track_ids[..index].contains(track_id) {
+            return Err(TimelineError::InvalidState(format!(
+                "clips linked together must be on different tracks; track {} has more than one",
+                track_id
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// How far a member has moved against its media since it was linked.
+pub(crate) fn drift(clip: &Clip) -> RationalTime {
+    let anchor = clip.link.map(|link| link.anchor).unwrap_or_else(|| clip.sync_point());
+    clip.sync_point() - anchor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TimeRange;
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn clip(start: i64, end: i64) -> Clip {
+        let source = TimeRange::from_start_end(frames(0), frames(end - start));
+        Clip::new("shot", "shot.mov", source, TimeRange::from_start_end(frames(start), frames(end)))
+    }
+
+    /// A picture and sound clip linked together, from 0 to 96.
+    fn linked() -> (Timeline, [Uuid; 2], [Uuid; 2]) {
+        let mut timeline = Timeline::new("edit", 24);
+        let mut tracks = [Track::new("V1", TrackKind::Video), Track::new("A1", TrackKind::Audio)];
+        let mut clips = [Uuid::nil(); 2];
+        for (track, id) in tracks.iter_mut().zip(clips.iter_mut()) {
+            let clip = clip(0, 96);
+            *id = clip.id;
+            track.add_clip(clip).unwrap();
+        }
+        let track_ids = [tracks[0].id, tracks[1].id];
+        for track in tracks {
+            timeline.add_track(track);
+        }
+        timeline.link_clips(&clips).unwrap();
+        (timeline, track_ids, clips)
+    }
+
+    #[test]
+    fn split_groups_are_stable_per_group_and_time() {
+        let link = ClipLink { group_id: Uuid::new_v4(), anchor: frames(0) };
+        let tail = link.split_at(&frames(48));
+        assert_eq!(tail, link.split_at(&frames(48)));
+        assert_eq!(tail.group_id.get_version_num(), 5);
+        assert_eq!(tail.anchor, link.anchor);
+        assert_ne!(tail.group_id, link.group_id);
+        assert_ne!(tail.group_id, link.split_at(&frames(49)).group_id);
+        let other = ClipLink { group_id: Uuid::new_v4(), ..link };
+        assert_ne!(tail.group_id, other.split_at(&frames(48)).group_id);
+    }
+
+    #[test]
+    fn splitting_linked_clips_links_the_tails_together() {
+        let (mut timeline, [video, audio], [picture, sound]) = linked();
+        let group = timeline.find_clip(picture).unwrap().1.link.unwrap().group_id;
+        let picture_tail = timeline.track_mut(video).unwrap().split_clip(picture, frames(48)).unwrap();
+        let sound_tail = timeline.track_mut(audio).unwrap().split_clip(sound, frames(48)).unwrap();
+
+        let tail_group = timeline.find_clip(picture_tail).unwrap().1.link.unwrap().group_id;
+        assert_ne!(tail_group, group);
+        let tails: Vec<Uuid> = timeline.linked_clips(tail_group).iter().map(|(_, c)| c.id).collect();
+        assert_eq!(tails, vec![picture_tail, sound_tail]);
+        assert_eq!(timeline.linked_clips(group).len(), 2);
+        assert!(timeline.sync_offsets().is_empty());
+    }
+
+    #[test]
+    fn link_clips_needs_two_clips_on_different_tracks() {
+        let (mut timeline, [video, _], [picture, sound]) = linked();
+        let second = clip(96, 192);
+        let second_id = second.id;
+        timeline.add_clip_to_track(video, second).unwrap();
+
+        assert!(matches!(timeline.link_clips(&[picture]), Err(TimelineError::InvalidState(_))));
+        assert!(matches!(timeline.link_clips(&[picture, second_id]), Err(TimelineError::InvalidState(_))));
+        assert!(matches!(timeline.link_clips(&[picture, Uuid::new_v4()]), Err(TimelineError::ClipNotFound(_))));
+
+        let group = timeline.link_clips(&[second_id, sound]).unwrap();
+        assert!(timeline.find_clip(picture).unwrap().1.link.is_some_and(|l| l.group_id != group));
+        assert_eq!(timeline.unlink_clips(group).len(), 2);
+        assert!(timeline.find_clip(sound).unwrap().1.link.is_none());
+    }
+}
@@ -1,6 +1,7 @@
-use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
-use crate::RationalTime;
+use crate::{Clip, RationalTime, TimeRange, Timeline, Track};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MarkerColor(String);
@@ -24,14 +25,27 @@ impl Default for MarkerColor {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarkerKind {
+    #[default]
+    Standard,
+    /// Exported as a navigation chapter in the delivered file.
+    Chapter,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Marker {
     pub id: Uuid,
     pub name: String,
     pub color: MarkerColor,
+    #[serde(default)]
+    pub kind: MarkerKind,
     pub time: RationalTime,
     pub duration: Option<RationalTime>,
     pub comment: Option<String>,
+    #[serde(default)]
+    pub completed: bool,
 }
 
 impl Marker {
@@ -40,9 +54,11 @@ impl Marker {
             id: Uuid::new_v4(),
             name: name.into(),
             color: MarkerColor::default(),
+            kind: MarkerKind::Standard,
             time,
             duration: None,
             comment: None,
+            completed: false,
         }
     }
 
@@ -51,6 +67,11 @@ impl Marker {
         self
     }
 
+    pub fn with_kind(mut self, kind: MarkerKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     pub fn with_duration(mut self, duration: RationalTime) -> Self {
         self.duration = Some(duration);
         self
@@ -60,4 +81,253 @@ impl Marker {
         self.comment = Some(comment.into());
         self
     }
+
+    pub fn with_completed(mut self, completed: bool) -> Self {
+        self.completed = completed;
+        self
+    }
+
+    /// The span the marker covers; zero length for point markers.
+    pub fn range(&self) -> TimeRange {
+        TimeRange::new(self.time, self.duration.unwrap_or(RationalTime::new(0, self.time.rate)))
+    }
+}
+
+impl Clip {
+    pub fn add_marker(&mut self, marker: Marker) {
+        let index = self.markers.partition_point(|m| m.time <= marker.time);
+        self.markers.insert(index, marker);
+    }
+
+    pub fn remove_marker(&mut self, marker_id: Uuid) -> Option<Marker> {
+        let pos = self.markers.iter().position(|m| m.id == marker_id)?;
+        Some(self.markers.remove(pos))
+    }
+
+    /// The clip's markers that fall inside its used media, moved from source
+    /// time to timeline time.
+    pub fn timeline_markers(&self) -> impl Iterator<Item = Marker> + '_ {
+        self.markers.iter().filter_map(|marker| {
+            let time = self.timeline_time_of(&marker.time)?;
+            Some(Marker { time, ..marker.clone() })
+        })
+    }
+}
+
+impl Track {
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Inserts a marker in time order, after any existing marker at the
+    /// same time.
+    pub fn add_marker(&mut self, marker: Marker) {
+        insert_sorted(&mut self.markers, marker);
+    }
+
+    pub fn remove_marker(&mut self, marker_id: Uuid) -> Option<Marker> {
+        let pos = self.markers.iter().position(|m| m.id == marker_id)?;
+        Some(self.markers.remove(pos))
+    }
+
+    /// The track's own markers and those of its clips, in timeline time.
+    pub fn markers_in_range(&self, range: &TimeRange) -> Vec<Marker> {
+        let mut markers = in_range(&self.markers, range).to_vec();
+        markers.extend(
+            self.clips_in_range(range)
+                .iter()
+                .flat_map(Clip::timeline_markers)
+                .filter(|m| range.contains(&m.time)),
+        );
+        markers.sort_by_key(|m| m.time);
+        markers
+    }
+}
+
+impl Timeline {
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Inserts a marker in time order, after any existing marker at the
+    /// same time.
+    pub fn add_marker(&mut self, marker: Marker) {
+        insert_sorted(&mut self.markers, marker);
+    }
+
+    pub fn remove_marker(&mut self, marker_id: Uuid) -> Option<Marker> {
+        let pos = self.markers.iter().position(|m| m.id == marker_id)?;
+        Some(self.markers.remove(pos))
+    }
+
+    /// Every marker starting inside `range`: the timeline's own, those on
+    /// enabled tracks and those on their clips, ordered by timeline time.
+    pub fn markers_in_range(&self, range: &TimeRange) -> Vec<Marker> {
+        let mut markers = in_range(&self.markers, range).to_vec();
+        for track in self.tracks.iter().filter(|t| t.enabled) {
+            markers.extend(track.markers_in_range(range));
+        }
+        markers.sort_by_key(|m| m.time);
+        markers
+    }
+
+    /// The first marker strictly after `time` at any level. Of markers at
+    /// the same time the timeline's own wins, then tracks in order.
+    pub fn next_marker_after(&self, time: &RationalTime) -> Option<Marker> {
+        self.all_markers().filter(|m| m.time > *time).min_by_key(|m| m.time)
+    }
+
+    /// The last marker strictly before `time` at any level, breaking ties
+    /// like [`Timeline::next_marker_after`].
+    pub fn prev_marker_before(&self, time: &RationalTime) -> Option<Marker> {
+        self.all_markers().filter(|m| m.time < *time).min_by_key(|m| Reverse(m.time))
+    }
+
+    pub(crate) fn all_markers(&self) -> impl Iterator<Item = Marker> + '_ {
+        let tracks = self.tracks.iter().filter(|t| t.enabled);
+        self.markers.iter().cloned().chain(tracks.flat_map(|track| {
+            track
+                .markers
+                .iter()
+                .cloned()
+                .chain(track.clips.iter().flat_map(Clip::timeline_markers))
+        }))
+    }
+}
+
+fn insert_sorted(markers: &mut Vec<Marker>, marker: Marker) {
+    let index = markers.partition_point(|m| m.time <= marker.time);
+    markers.insert(index, marker);
+}
+
+fn in_range<'a>(markers: &'a [Marker], range: &TimeRange) -> &'a [Marker] {
+    let first = markers.partition_point(|m| m.time < range.start);
+    let last = markers.partition_point(|m| m.time < range.end());
+    &markers[first..last.max(first)]
+}
+
+pub(crate) fn deserialize_markers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Marker>, D::Error> {
+    let mut markers = Vec::<Marker>::deserialize(deserializer)?;
+    markers.sort_by_key(|m| m.time);
+    Ok(markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::TrackKind;
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn saved_clip(markers: serde_json::Value) -> serde_json::Value {
+        let source = TimeRange::new(frames(100), frames(48));
+        let clip = Clip::new("shot", "shot.mov", source, TimeRange::new(frames(0), frames(48)));
+        let mut saved = serde_json::to_value(clip).unwrap();
+        saved["markers"] = markers;
+        saved
+    }
+
+    #[test]
+    fn legacy_clip_marker_names_load_at_the_source_start() {
+        let clip: Clip = serde_json::from_value(saved_clip(json!(["good take", "focus"]))).unwrap();
+        let names: Vec<_> = clip.markers.iter().map(|m| (m.name.as_str(), m.time)).collect();
+        assert_eq!(names, vec![("good take", frames(100)), ("focus", frames(100))]);
+    }
+
+    #[test]
+    fn clip_markers_are_sorted_on_load() {
+        let late = Marker::new("late", frames(130));
+        let early = Marker::new("early", frames(110));
+        let mut clip: Clip = serde_json::from_value(saved_clip(json!([late, "named", early]))).unwrap();
+        clip.add_marker(Marker::new("middle", frames(120)));
+        let names: Vec<_> = clip.markers.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["named", "early", "middle", "late"]);
+    }
+
+    /// Timeline markers at 10 and 40, a track marker at 40 and a clip
+    /// placed at 24 with a marker 8 frames into the media it shows, plus a
+    /// disabled track with a marker at 20.
+    fn marked_timeline() -> Timeline {
+        let mut timeline = Timeline::new("markers", 24);
+        timeline.add_marker(Marker::new("ten", frames(10)));
+        timeline.add_marker(Marker::new("forty", frames(40)));
+
+        let mut track = Track::new("V1", TrackKind::Video);
+        track.add_marker(Marker::new("track forty", frames(40)));
+        let mut clip = Clip::new("shot", "shot.mov", TimeRange::new(frames(100), frames(48)), TimeRange::new(frames(24), frames(48)));
+        clip.add_marker(Marker::new("clip", frames(108)));
+        clip.add_marker(Marker::new("unused", frames(10)));
+        track.add_clip(clip).unwrap();
+        timeline.add_track(track);
+
+        let mut hidden = Track::new("V2", TrackKind::Video);
+        hidden.add_marker(Marker::new("hidden", frames(20)));
+        hidden.enabled = false;
+        timeline.add_track(hidden);
+        timeline
+    }
+
+    fn names(markers: &[Marker]) -> Vec<&str> {
+        markers.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn range_queries_gather_every_level_in_timeline_time() {
+        let timeline = marked_timeline();
+        let all = timeline.markers_in_range(&TimeRange::new(frames(0), frames(100)));
+        assert_eq!(names(&all), vec!["ten", "clip", "forty", "track forty"]);
+        assert_eq!(all[1].time, frames(32));
+
+        let end_exclusive = timeline.markers_in_range(&TimeRange::new(frames(10), frames(22)));
+        assert_eq!(names(&end_exclusive), vec!["ten"]);
+        let track = &timeline.tracks[0];
+        assert_eq!(names(&track.markers_in_range(&TimeRange::new(frames(30), frames(11)))), vec!["clip", "track forty"]);
+    }
+
+    #[test]
+    fn ranged_markers_are_found_by_their_start() {
+        let mut timeline = Timeline::new("markers", 24);
+        timeline.add_marker(Marker::new("span", frames(10)).with_duration(frames(20)));
+        assert_eq!(timeline.markers()[0].range(), TimeRange::new(frames(10), frames(20)));
+        assert!(timeline.markers_in_range(&TimeRange::new(frames(15), frames(10))).is_empty());
+        assert_eq!(timeline.markers_in_range(&TimeRange::new(frames(5), frames(10))).len(), 1);
+    }
+
+    #[test]
+    fn stepping_between_markers_skips_disabled_tracks() {
+        let timeline = marked_timeline();
+        assert_eq!(timeline.next_marker_after(&frames(10)).unwrap().name, "clip");
+        assert_eq!(timeline.next_marker_after(&frames(0)).unwrap().name, "ten");
+        assert_eq!(timeline.prev_marker_before(&frames(32)).unwrap().name, "ten");
+        assert_eq!(timeline.prev_marker_before(&frames(10)), None);
+        assert_eq!(timeline.next_marker_after(&frames(40)), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_timeline_then_tracks_in_order() {
+        let timeline = marked_timeline();
+        assert_eq!(timeline.next_marker_after(&frames(32)).unwrap().name, "forty");
+        assert_eq!(timeline.prev_marker_before(&frames(41)).unwrap().name, "forty");
+
+        let mut track = Track::new("V1", TrackKind::Video);
+        track.add_marker(Marker::new("first", frames(5)));
+        track.add_marker(Marker::new("second", frames(5)));
+        assert_eq!(names(track.markers()), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn completed_flags_are_kept_and_default_to_open() {
+        let done = Marker::new("fix colour", frames(12)).with_completed(true);
+        let saved = serde_json::to_value(&done).unwrap();
+        assert_eq!(saved["completed"], json!(true));
+        assert_eq!(serde_json::from_value::<Marker>(saved.clone()).unwrap(), done);
+
+        let mut old = saved;
+        old.as_object_mut().unwrap().remove("completed");
+        assert!(!serde_json::from_value::<Marker>(old).unwrap().completed);
+        assert!(!Marker::new("todo", frames(0)).completed);
+    }
 }
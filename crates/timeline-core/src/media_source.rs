@@ -1,27 +1,54 @@
 use serde::{Deserialize, Serialize};
-use crate::RationalTime;
+use uuid::Uuid;
+use crate::{FrameRate, RationalTime, TimeRange};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MediaSource {
     pub path: String,
     pub hash: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<MediaMetadata>,
 }
 
 impl MediaSource {
     pub fn new(path: impl Into<String>) -> Self {
-        Self { path: path.into(), hash: None }
+        Self { path: path.into(), hash: None, metadata: None }
     }
 
     pub fn with_hash(mut self, hash: impl Into<String>) -> Self {
         self.hash = Some(hash.into());
         self
     }
+
+    pub fn with_metadata(mut self, metadata: MediaMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// The span of source time that exists on disk, when the duration is known.
+    pub fn available_range(&self) -> Option<TimeRange> {
+        self.metadata.as_ref().map(|m| {
+            TimeRange::new(RationalTime::new(0, m.duration.rate), m.duration)
+        })
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl From<String> for MediaSource {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<&str> for MediaSource {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MediaMetadata {
     pub duration: RationalTime,
-    pub frame_rate: u32,
+    pub frame_rate: FrameRate,
     pub width: u32,
     pub height: u32,
     pub has_audio: bool,
@@ -32,7 +59,7 @@ impl Default for MediaMetadata {
     fn default() -> Self {
         Self {
             duration: RationalTime::new(0, 24),
-            frame_rate: 24,
+            frame_rate: FrameRate::FPS_24,
             width: 1920,
             height: 1080,
             has_audio: true,
@@ -40,3 +67,100 @@ impl Default for MediaMetadata {
         }
     }
 }
+
+/// Another timeline of the same project used as a clip's media, i.e. a
+/// compound clip. `duration` mirrors the nested timeline's and is kept up
+/// to date by `Project`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SequenceSource {
+    pub timeline_id: Uuid,
+    pub duration: RationalTime,
+}
+
+/// One angle of a multicam source stored on the clip's timeline.
+/// `available` mirrors the part of the multicam clock the angle has media
+/// for and is kept up to date by `Timeline`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MulticamRef {
+    pub multicam_id: Uuid,
+    pub active_angle: usize,
+    #[serde(default)]
+    pub available: Option<TimeRange>,
+}
+
+/// What a clip plays. Serialized untagged so plain media sources read the
+/// same as before compound clips existed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClipSource {
+    Media(MediaSource),
+    Sequence(SequenceSource),
+    Multicam(MulticamRef),
+}
+
+impl ClipSource {
+    /// The file the clip plays. Compound and multicam clips name theirs
+    /// through their timeline.
+    pub fn media(&self) -> Option<&MediaSource> {
+        match self {
+            ClipSource::Media(media) => Some(media),
+            _ => None,
+        }
+    }
+
+    pub fn multicam(&self) -> Option<&MulticamRef> {
+        match self {
+            ClipSource::Multicam(multicam) => Some(multicam),
+            _ => None,
+        }
+    }
+
+    pub fn sequence_id(&self) -> Option<Uuid> {
+        match self {
+            ClipSource::Sequence(sequence) => Some(sequence.timeline_id),
+            _ => None,
+        }
+    }
+
+    /// The span of source time the clip can draw from, when it is known.
+    pub fn available_range(&self) -> Option<TimeRange> {
+        match self {
+            ClipSource::Media(media) => media.available_range(),
+            ClipSource::Sequence(sequence) => Some(TimeRange::new(
+                RationalTime::new(0, sequence.duration.rate),
+                sequence.duration,
+            )),
+            ClipSource::Multicam(multicam) => multicam.available,
+        }
+    }
+}
+
+impl From<MediaSource> for ClipSource {
+    fn from(media: MediaSource) -> Self {
+        ClipSource::Media(media)
+    }
+}
+
+impl From<SequenceSource> for ClipSource {
+    fn from(sequence: SequenceSource) -> Self {
+        ClipSource::Sequence(sequence)
+    }
+}
+
+impl From<MulticamRef> for ClipSource {
+    fn from(multicam: MulticamRef) -> Self {
+        ClipSource::Multicam(multicam)
+    }
+}
+
+impl From<String> for ClipSource {
+    fn from(path: String) -> Self {
+        ClipSource::Media(path.into())
+    }
+}
+
+impl From<&str> for ClipSource {
+    fn from(path: &str) -> Self {
+        ClipSource::Media(path.into())
+    }
+}
@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{Clip, ClipSource, EditResult, MediaSource, MulticamRef, ParameterValue, RationalTime, TimeRange, Timecode, Timeline, TimelineError, Track};
+
+/// One camera of a multicam source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MulticamAngle {
+    pub name: String,
+    pub source: MediaSource,
+    /// Where the angle's first frame falls on the multicam clock.
+    pub offset: RationalTime,
+}
+
+/// Several recordings of one event lined up on a shared clock. Stored once
+/// on a timeline; each clip playing it holds a [`MulticamRef`] to the angle
+/// it shows and measures its `source_range` on that clock.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MulticamSource {
+    pub id: Uuid,
+    pub name: String,
+    pub angles: Vec<MulticamAngle>,
+}
+
+impl MulticamSource {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            angles: Vec::new(),
+        }
+    }
+
+    /// Adds an angle whose first frame is `offset` into the clock.
+    pub fn with_angle(mut self, name: impl Into<String>, source: MediaSource, offset: RationalTime) -> Self {
+        self.angles.push(MulticamAngle { name: name.into(), source, offset });
+        self
+    }
+
+    /// Lines the angles up by the timecode of their first frames. The
+    /// earliest angle starts the clock.
+    pub fn from_timecodes(
+        name: impl Into<String>,
+        angles: Vec<(String, MediaSource, Timecode)>,
+    ) -> Result<Self, TimelineError> {
+        let start = angles
+            .iter()
+            .map(|(_, _, timecode)| timecode.to_time())
+            .min()
+            .ok_or_else(|| TimelineError::InvalidState("a multicam source needs at least one angle".to_string()))?;
+        Ok(angles
+            .into_iter()
+            .fold(Self::new(name), |multicam, (name, source, timecode)| {
+                multicam.with_angle(name, source, timecode.to_time() - start)
+            }))
+    }
+
+    pub fn angle(&self, index: usize) -> Option<&MulticamAngle> {
+        self.angles.get(index)
+    }
+
+    /// The part of the clock angle `index` has media for, when its
+    /// duration is known.
+    pub fn angle_range(&self, index: usize) -> Option<TimeRange> {
+        let angle = self.angles.get(index)?;
+        angle.source.available_range().map(|range| range.shifted_by(angle.offset))
+    }
+
+    /// The source of a clip showing angle `index`.
+    pub fn reference(&self, index: usize) -> Result<MulticamRef, TimelineError> {
+        if index >= self.angles.len() {
+            return Err(TimelineError::AngleNotFound(index));
+        }
+        Ok(MulticamRef {
+            multicam_id: self.id,
+            active_angle: index,
+            available: self.angle_range(index),
+        })
+    }
+
+    /// A clip of angle `index` for as long as it has media, placed at
+    /// `timeline_start`. The source must be added to the clip's timeline
+    /// with [`Timeline::add_multicam`].
+    pub fn clip(&self, index: usize, timeline_start: RationalTime) -> Result<Clip, TimelineError> {
+        let reference = self.reference(index)?;
+        let range = reference
+            .available
+            .ok_or_else(|| TimelineError::MediaSourceNotFound(self.angles[index].source.path.clone()))?;
+        let timeline_range = TimeRange::new(timeline_start, range.duration);
+        Ok(Clip::new(self.name.clone(), reference, range, timeline_range))
+    }
+
+    fn check_played_by(&self, clip: &Clip) -> Result<MulticamRef, TimelineError> {
+        clip.source
+            .multicam()
+            .copied()
+            .filter(|reference| reference.multicam_id == self.id)
+            .ok_or_else(|| TimelineError::InvalidState(format!("clip {} does not play multicam {}", clip.id, self.id)))
+    }
+}
+
+impl Clip {
+    /// Switches a clip of `multicam` to another camera.
+    pub fn set_angle(&mut self, multicam: &MulticamSource, angle: usize) -> Result<(), TimelineError> {
+        multicam.check_played_by(self)?;
+        self.source = multicam.reference(angle)?.into();
+        Ok(())
+    }
+
+    /// The plain clip of the angle a clip of `multicam` shows, with every
+    /// source time moved from the multicam clock onto the angle's own
+    /// media. Fails when the clip reaches outside that media. Other clips
+    /// are returned as they are.
+    pub fn flatten_multicam(&self, multicam: &MulticamSource) -> Result<Clip, TimelineError> {
+        if self.source.multicam().is_none() {
+            return Ok(self.clone());
+        }
+        let index = multicam.check_played_by(self)?.active_angle;
+        let angle = multicam.angle(index).ok_or(TimelineError::AngleNotFound(index))?;
+        let shift = -angle.offset;
+        let seconds = shift.to_seconds();
+
+        let mut clip = self.clone();
+        clip.source = angle.source.clone().into();
+        clip.source_range = clip.source_range.shifted_by(shift);
+        let within_media = match angle.source.available_range() {
+            Some(available) => available.contains_range(&clip.source_range),
+            None => clip.source_range.start.value >= 0,
+        };
+        if !within_media {
+            return Err(TimelineError::InsufficientMedia(self.id));
+        }
+        clip.freeze_frame = clip.freeze_frame.map(|frame| frame + shift);
+        if let Some(curve) = &mut clip.time_remap {
+            curve.value += seconds;
+            for keyframe in &mut curve.keyframes {
+                if let ParameterValue::Number(value) = &mut keyframe.value {
+                    *value += seconds;
+                }
+            }
+        }
+        for marker in &mut clip.markers {
+            marker.time = marker.time + shift;
+        }
+        Ok(clip)
+    }
+}
+
+impl Track {
+    /// Cuts the clip of `multicam` under `time` and shows `angle` from
+    /// there on. A clip that already starts at `time` just switches.
+    pub fn angle_cut(&mut self, time: RationalTime, multicam: &MulticamSource, angle: usize) -> Result<EditResult, TimelineError> {
+        self.apply_edit(|clips| {
+            let mut index = clips
+                .iter()
+                .position(|c| c.timeline_range.contains(&time) && multicam.check_played_by(c).is_ok())
+                .ok_or_else(|| TimelineError::InvalidState(format!("no multicam clip at {}s", time.to_seconds())))?;
+
+            if clips[index].timeline_range.start != time {
+                let tail = clips[index].split(time)?;
+                clips.push(tail);
+                index = clips.len() - 1;
+            } else if clips[index].locked {
+                return Err(TimelineError::ClipLocked(clips[index].id));
+            }
+
+            let clip = &mut clips[index];
+            clip.set_angle(multicam, angle)?;
+            // Placement checks only see moved clips, so a plain switch
+            // checks the new angle's media here.
+            match clip.source.available_range() {
+                Some(available) if !available.contains_range(&clip.source_range) => {
+                    Err(TimelineError::InsufficientMedia(clip.id))
+                }
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+impl Timeline {
+    /// Stores `multicam` for the timeline's clips to play, replacing the
+    /// source with the same id and bringing the clips that play it up to
+    /// date.
+    pub fn add_multicam(&mut self, multicam: MulticamSource) -> Uuid {
+        let id = multicam.id;
+        for clip in self.tracks.iter_mut().flat_map(|t| t.clips.iter_mut()) {
+            if let ClipSource::Multicam(reference) = &mut clip.source {
+                if reference.multicam_id == id {
+                    reference.available = multicam.angle_range(reference.active_angle);
+                }
+            }
+        }
+        self.multicams.insert(id, multicam);
+        id
+    }
+
+    pub fn multicam(&self, multicam_id: Uuid) -> Option<&MulticamSource> {
+        self.multicams.get(&multicam_id)
+    }
+
+    pub fn multicams(&self) -> impl Iterator<Item = &MulticamSource> {
+        self.multicams.values()
+    }
+
+    /// The camera a multicam clip of this timeline shows.
+    pub fn clip_angle(&self, clip: &Clip) -> Option<&MulticamAngle> {
+        let reference = clip.source.multicam()?;
+        self.multicam(reference.multicam_id)?.angle(reference.active_angle)
+    }
+
+    /// An angle cut on the multicam clip of `track_id` at `time`.
+    pub fn angle_cut(&mut self, track_id: Uuid, time: RationalTime, angle: usize) -> Result<EditResult, TimelineError> {
+        let Timeline { tracks, multicams, .. } = self;
+        let track = tracks
+            .iter_mut()
+            .find(|t| t.id == track_id)
+            .ok_or(TimelineError::TrackNotFound(track_id))?;
+        let multicam_id = track
+            .clips
+            .iter()
+            .filter(|c| c.timeline_range.contains(&time))
+            .find_map(|c| c.source.multicam())
+            .ok_or_else(|| TimelineError::InvalidState(format!("no multicam clip at {}s", time.to_seconds())))?
+            .multicam_id;
+        let multicam = multicams.get(&multicam_id).ok_or(TimelineError::MulticamNotFound(multicam_id))?;
+        track.angle_cut(time, multicam, angle)
+    }
+
+    /// A copy with every multicam clip replaced by a plain clip of its
+    /// active angle, for export.
+    pub fn flatten_multicam(&self) -> Result<Timeline, TimelineError> {
+        let mut flat = self.clone();
+        for clip in flat.tracks.iter_mut().flat_map(|t| t.clips.iter_mut()) {
+            if let Some(reference) = clip.source.multicam() {
+                let multicam = self
+                    .multicam(reference.multicam_id)
+                    .ok_or(TimelineError::MulticamNotFound(reference.multicam_id))?;
+                *clip = clip.flatten_multicam(multicam)?;
+            }
+        }
+        flat.multicams.clear();
+        Ok(flat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{History, MediaMetadata, TrackKind};
+
+    fn seconds(value: i64) -> RationalTime {
+        RationalTime::new(value * 24, 24)
+    }
+
+    fn camera(path: &str, duration: i64) -> MediaSource {
+        MediaSource::new(path).with_metadata(MediaMetadata { duration: seconds(duration), ..MediaMetadata::default() })
+    }
+
+    /// Two cameras, the second started ten seconds into the first, with a
+    /// clip of the first angle from 10s to 60s of the clock at the start of
+    /// V1.
+    fn timeline() -> (Timeline, Uuid) {
+        let multicam = MulticamSource::new("podcast")
+            .with_angle("wide", camera("wide.mov", 60), seconds(0))
+            .with_angle("close", camera("close.mov", 60), seconds(10));
+        let mut clip = multicam.clip(0, seconds(0)).unwrap();
+        clip.trim(seconds(0), seconds(50)).unwrap();
+        clip.slip(seconds(10));
+
+        let mut timeline = Timeline::new("edit", 24);
+        let mut track = Track::new("V1", TrackKind::Video);
+        let track_id = track.id;
+        track.add_clip(clip).unwrap();
+        timeline.add_track(track);
+        timeline.add_multicam(multicam);
+        (timeline, track_id)
+    }
+
+    #[test]
+    fn angle_cut_switches_from_the_cut_on() {
+        let (mut timeline, track_id) = timeline();
+        timeline.angle_cut(track_id, seconds(20), 1).unwrap();
+
+        let clips = timeline.track(track_id).unwrap().clips();
+        let angles: Vec<_> = clips.iter().map(|c| timeline.clip_angle(c).unwrap().name.as_str()).collect();
+        assert_eq!(angles, vec!["wide", "close"]);
+        assert_eq!(clips[1].source_range, TimeRange::new(seconds(30), seconds(30)));
+        assert!(matches!(timeline.angle_cut(track_id, seconds(45), 2), Err(TimelineError::AngleNotFound(2))));
+    }
+
+    #[test]
+    fn angle_cut_edits_only_the_given_track() {
+        let (mut timeline, lower_id) = timeline();
+        let mut upper = Track::new("V2", TrackKind::Video);
+        let upper_id = upper.id;
+        upper.add_clip(timeline.track(lower_id).unwrap().clips()[0].clone()).unwrap();
+        timeline.tracks.insert(0, upper);
+
+        timeline.angle_cut(lower_id, seconds(20), 1).unwrap();
+        assert_eq!(timeline.track(lower_id).unwrap().clips().len(), 2);
+        assert_eq!(timeline.track(upper_id).unwrap().clips().len(), 1);
+
+        let missing = Uuid::new_v4();
+        assert!(matches!(timeline.angle_cut(missing, seconds(20), 1), Err(TimelineError::TrackNotFound(id)) if id == missing));
+        assert!(matches!(timeline.angle_cut(lower_id, seconds(55), 1), Err(TimelineError::InvalidState(_))));
+    }
+
+    #[test]
+    fn clips_share_one_stored_source() {
+        let (mut timeline, track_id) = timeline();
+        timeline.angle_cut(track_id, seconds(20), 1).unwrap();
+        timeline.angle_cut(track_id, seconds(40), 0).unwrap();
+
+        let json = serde_json::to_string(&timeline).unwrap();
+        assert_eq!(json.matches("close.mov").count(), 1);
+        let loaded: Timeline = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, timeline);
+    }
+
+    #[test]
+    fn flattening_moves_source_times_onto_each_angle() {
+        let (mut timeline, track_id) = timeline();
+        timeline.angle_cut(track_id, seconds(20), 1).unwrap();
+
+        let flat = timeline.flatten_multicam().unwrap();
+        assert_eq!(flat.multicams().count(), 0);
+        let clips = flat.track(track_id).unwrap().clips();
+        assert_eq!(clips[0].source.media().unwrap().path, "wide.mov");
+        assert_eq!(clips[0].source_range, TimeRange::new(seconds(10), seconds(20)));
+        assert_eq!(clips[1].source.media().unwrap().path, "close.mov");
+        assert_eq!(clips[1].source_range, TimeRange::new(seconds(20), seconds(30)));
+    }
+
+    #[test]
+    fn clips_past_the_end_of_their_angle_cannot_flatten() {
+        let (mut timeline, track_id) = timeline();
+        timeline.angle_cut(track_id, seconds(20), 1).unwrap();
+        // The close camera turns out to have stopped ten seconds early.
+        let mut multicam = timeline.multicams().next().unwrap().clone();
+        multicam.angles[1].source = camera("close.mov", 20);
+        timeline.multicams.insert(multicam.id, multicam);
+
+        let close_id = timeline.track(track_id).unwrap().clips()[1].id;
+        assert!(matches!(timeline.flatten_multicam(), Err(TimelineError::InsufficientMedia(id)) if id == close_id));
+    }
+
+    #[test]
+    fn clips_of_a_missing_source_cannot_flatten() {
+        let (mut timeline, track_id) = timeline();
+        let multicam_id = timeline.multicams().next().unwrap().id;
+        timeline.multicams.clear();
+        assert!(matches!(timeline.flatten_multicam(), Err(TimelineError::MulticamNotFound(id)) if id == multicam_id));
+        assert!(matches!(timeline.angle_cut(track_id, seconds(20), 1), Err(TimelineError::MulticamNotFound(_))));
+    }
+
+    #[test]
+    fn adding_a_source_is_undoable() {
+        let (mut timeline, track_id) = timeline();
+        let mut history = History::new(10);
+        let original = timeline.clone();
+        let multicam = MulticamSource::new("stage").with_angle("left", camera("left.mov", 30), seconds(0));
+        history
+            .perform(&mut timeline, "Add multicam clip", |timeline| {
+                let clip = multicam.clip(0, seconds(50))?;
+                timeline.add_multicam(multicam.clone());
+                timeline.add_clip_to_track(track_id, clip)
+            })
+            .unwrap();
+        let edited = timeline.clone();
+
+        history.undo(&mut timeline).unwrap();
+        assert_eq!(timeline, original);
+        history.redo(&mut timeline).unwrap();
+        assert_eq!(timeline, edited);
+        assert_eq!(timeline.multicams().count(), 2);
+    }
+}
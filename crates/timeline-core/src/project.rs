@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{Clip, ClipLink, ClipSource, RationalTime, SequenceSource, TimeRange, Timeline, TimelineError, Track, TrackKind};
+
+/// The timelines of one project. Clips on any of them may use another as
+/// their source, which is how compound clips and reusable sub-sequences
+/// are built.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub id: Uuid,
+    pub name: String,
+    timelines: Vec<Timeline>,
+}
+
+impl Project {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            timelines: Vec::new(),
+        }
+    }
+
+    pub fn timelines(&self) -> &[Timeline] {
+        &self.timelines
+    }
+
+    pub fn timeline(&self, timeline_id: Uuid) -> Option<&Timeline> {
+        self.timelines.iter().find(|t| t.id == timeline_id)
+    }
+
+    /// Adds a timeline, failing if its compound clips point at timelines
+    /// that are missing or would nest it inside itself.
+    pub fn add_timeline(&mut self, timeline: Timeline) -> Result<Uuid, TimelineError> {
+        let id = timeline.id;
+        self.timelines.push(timeline);
+        if let Err(err) = self.check_references().and_then(|_| self.refresh_sequence_durations()) {
+            self.timelines.pop();
+            return Err(err);
+        }
+        Ok(id)
+    }
+
+    /// Removes a timeline that no compound clip uses.
+    pub fn remove_timeline(&mut self, timeline_id: Uuid) -> Result<Timeline, TimelineError> {
+        let pos = self
+            .timelines
+            .iter()
+            .position(|t| t.id == timeline_id)
+            .ok_or(TimelineError::TimelineNotFound(timeline_id))?;
+        if let Some(user) = self.timelines.iter().find(|t| references(t).any(|id| id == timeline_id)) {
+            return Err(TimelineError::InvalidState(format!(
+                "timeline {} is used by compound clips in {}",
+                timeline_id, user.id
+            )));
+        }
+        Ok(self.timelines.remove(pos))
+    }
+
+    /// Runs `edit` on one timeline, then re-checks nesting and updates the
+    /// length of every compound clip built from it. Nothing changes if
+    /// either step fails.
+    pub fn update_timeline<T, F>(&mut self, timeline_id: Uuid, edit: F) -> Result<T, TimelineError>
+    where
+        F: FnOnce(&mut Timeline) -> Result<T, TimelineError>,
+    {
+        let pos = self
+            .timelines
+            .iter()
+            .position(|t| t.id == timeline_id)
+            .ok_or(TimelineError::TimelineNotFound(timeline_id))?;
+        let snapshot = self.timelines[pos].clone();
+        let result = edit(&mut self.timelines[pos]).and_then(|output| {
+            self.check_references()?;
+            self.refresh_sequence_durations()?;
+            Ok(output)
+        });
+        match result {
+            Ok(output) => Ok(output),
+            Err(err) => {
+                self.timelines[pos] = snapshot;
+                Err(err)
+            }
+        }
+    }
+
+    /// A clip playing the whole of `timeline_id`, placed at time zero.
+    pub fn compound_clip(&self, timeline_id: Uuid) -> Result<Clip, TimelineError> {
+        let nested = self.timeline(timeline_id).ok_or(TimelineError::TimelineNotFound(timeline_id))?;
+        let duration = nested.duration();
+        let range = TimeRange::new(RationalTime::new(0, duration.rate), duration);
+        let source = SequenceSource { timeline_id, duration };
+        Ok(Clip::new(nested.name.clone(), source, range, range))
+    }
+
+    /// Fails if a compound clip points at a timeline outside the project or
+    /// if following compound clips ever leads back to where it started.
+    pub fn check_references(&self) -> Result<(), TimelineError> {
+        let mut state: HashMap<Uuid, Visit> = HashMap::new();
+        for timeline in &self.timelines {
+            self.visit(timeline.id, &mut state)?;
+        }
+        Ok(())
+    }
+
+    fn visit(&self, timeline_id: Uuid, state: &mut HashMap<Uuid, Visit>) -> Result<(), TimelineError> {
+        match state.get(&timeline_id) {
+            Some(Visit::Done) => return Ok(()),
+            Some(Visit::InProgress) => return Err(TimelineError::CircularReference(timeline_id)),
+            None => {}
+        }
+        let timeline = self.timeline(timeline_id).ok_or(TimelineError::TimelineNotFound(timeline_id))?;
+        state.insert(timeline_id, Visit::InProgress);
+        for nested in references(timeline) {
+            self.visit(nested, state)?;
+        }
+        state.insert(timeline_id, Visit::Done);
+        Ok(())
+    }
+
+    /// Updates the length every compound clip records for its timeline.
+    /// Fails, changing nothing, if a timeline has become too short for a
+    /// compound clip that plays it.
+    fn refresh_sequence_durations(&mut self) -> Result<(), TimelineError> {
+        let durations: HashMap<Uuid, RationalTime> = self.timelines.iter().map(|t| (t.id, t.duration())).collect();
+        for clip in self.timelines.iter().flat_map(|t| t.tracks.iter()).flat_map(|t| t.clips.iter()) {
+            let Some(duration) = clip.source.sequence_id().and_then(|id| durations.get(&id)) else {
+                continue;
+            };
+            if clip.source_range.start.value < 0 || clip.source_range.end() > *duration {
+                return Err(TimelineError::InsufficientMedia(clip.id));
+            }
+        }
+        for track in self.timelines.iter_mut().flat_map(|t| t.tracks.iter_mut()) {
+            for clip in &mut track.clips {
+                if let ClipSource::Sequence(sequence) = &mut clip.source {
+                    if let Some(duration) = durations.get(&sequence.timeline_id) {
+                        sequence.duration = *duration;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The media clip showing at `time` on `timeline_id`, looking inside
+    /// compound clips until it reaches one that plays a file. Returns the
+    /// timeline the clip sits on along with the clip.
+    pub fn clip_at_time(&self, timeline_id: Uuid, time: &RationalTime) -> Option<(&Timeline, &Clip)> {
+        let mut timeline = self.timeline(timeline_id)?;
+        let mut time = *time;
+        // Bounded by the number of timelines, since references are acyclic.
+        for _ in 0..=self.timelines.len() {
+            let clip = timeline.clip_at_time(&time)?;
+            match clip.source.sequence_id() {
+                None => return Some((timeline, clip)),
+                Some(nested) => {
+                    time = clip.source_time_at(&time);
+                    timeline = self.timeline(nested)?;
+                }
+            }
+        }
+        None
+    }
+
+    /// A copy of `timeline_id` with every compound clip replaced by the
+    /// clips it plays, trimmed to the part in use and moved into place.
+    /// Each nested track lands on its own track directly below the track
+    /// that held the compound clip. Compound clips must play at normal
+    /// speed; their own effects, properties and transitions are not
+    /// carried over.
+    pub fn flatten(&self, timeline_id: Uuid) -> Result<Timeline, TimelineError> {
+        self.check_references()?;
+        let timeline = self.timeline(timeline_id).ok_or(TimelineError::TimelineNotFound(timeline_id))?;
+
+        let mut flat = Timeline { tracks: Vec::new(), ..timeline.clone() };
+        for track in &timeline.tracks {
+            // Unlocked while it is rebuilt, since locked tracks refuse
+            // transitions.
+            let mut base = Track { clips: Vec::new(), transitions: Vec::new(), locked: false, ..track.clone() };
+            let mut lanes: Vec<((usize, TrackKind), Track)> = Vec::new();
+            let mut kept = Vec::new();
+
+            for clip in &track.clips {
+                let Some(nested_id) = clip.source.sequence_id() else {
+                    kept.push(clip.clone());
+                    continue;
+                };
+                if clip.is_retimed() {
+                    return Err(TimelineError::InvalidState(format!(
+                        "compound clip {} is retimed and cannot be flattened",
+                        clip.id
+                    )));
+                }
+                if !clip.enabled {
+                    continue;
+                }
+                let nested = self.flatten(nested_id)?;
+                flat.multicams.extend(nested.multicams.iter().map(|(id, multicam)| (*id, multicam.clone())));
+                let mut groups = HashMap::new();
+                for (index, nested_track) in nested.tracks.iter().filter(|t| t.enabled).enumerate() {
+                    let key = (index, nested_track.kind);
+                    let lane = match lanes.iter().position(|(k, _)| *k == key) {
+                        Some(pos) => pos,
+                        None => {
+                            let name = format!("{} / {}", track.name, nested_track.name);
+                            lanes.push((key, Track::new(name, nested_track.kind)));
+                            lanes.len() - 1
+                        }
+                    };
+                    place_nested(&mut lanes[lane].1, nested_track, clip, &mut groups)?;
+                }
+            }
+
+            for clip in kept {
+                base.add_clip(clip)?;
+            }
+            for transition in &track.transitions {
+                let (Some(from), Some(to)) = (transition.from_clip_id, transition.to_clip_id) else {
+                    continue;
+                };
+                if base.clip(from).is_some() && base.clip(to).is_some() {
+                    base.add_transition(from, to, transition.clone())?;
+                }
+            }
+            base.locked = track.locked;
+            flat.tracks.push(base);
+            flat.tracks.extend(lanes.into_iter().map(|(_, lane)| lane));
+        }
+        Ok(flat)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+fn references(timeline: &Timeline) -> impl Iterator<Item = Uuid> + '_ {
+    timeline
+        .tracks
+        .iter()
+        .flat_map(|t| t.clips.iter())
+        .filter_map(|c| c.source.sequence_id())
+}
+
+/// Copies the clips of `nested_track` that `compound` shows onto `lane`,
+/// moved from nested time to the outer timeline. Clips get new ids since
+/// the same sequence may be used many times, and link groups are renamed
+/// through `groups` for the same reason.
+fn place_nested(
+    lane: &mut Track,
+    nested_track: &Track,
+    compound: &Clip,
+    groups: &mut HashMap<Uuid, Uuid>,
+) -> Result<(), TimelineError> {
+    let window = compound.source_range;
+    let shift = compound.timeline_range.start - window.start;
+    let mut ids = HashMap::new();
+    for clip in nested_track.clips_in_range(&window).iter().filter(|c| c.enabled) {
+        let Some(visible) = clip.timeline_range.intersection(&window) else { continue };
+        let mut placed = clip.clone();
+        placed.trim(visible.start, visible.duration)?;
+        placed.timeline_range = placed.timeline_range.shifted_by(shift);
+        placed.id = Uuid::new_v4();
+        placed.link = placed.link.map(|link| ClipLink {
+            group_id: *groups.entry(link.group_id).or_insert_with(Uuid::new_v4),
+            anchor: link.anchor + shift,
+        });
+        ids.insert(clip.id, placed.id);
+        lane.add_clip(placed)?;
+    }
+    for transition in &nested_track.transitions {
+        let from = transition.from_clip_id.and_then(|id| ids.get(&id));
+        let to = transition.to_clip_id.and_then(|id| ids.get(&id));
+        let Some(edit_point) = transition.to_clip_id.and_then(|id| nested_track.clip(id)).map(|c| c.timeline_range.start) else {
+            continue;
+        };
+        let span = TimeRange::from_start_end(edit_point - transition.in_point, edit_point + transition.out_point());
+        // Transitions cut by the compound clip's edges are dropped.
+        if !window.contains_range(&span) {
+            continue;
+        }
+        if let (Some(&from), Some(&to)) = (from, to) {
+            let mut transition = transition.clone();
+            transition.id = Uuid::new_v4();
+            lane.add_transition(from, to, transition)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MediaMetadata, MediaSource, Transition};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// A timeline with one video track holding `shot.mov` from 0 to `end`.
+    fn scene(name: &str, end: i64) -> (Timeline, Uuid, Uuid) {
+        let mut timeline = Timeline::new(name, 24);
+        let mut track = Track::new("V1", TrackKind::Video);
+        let track_id = track.id;
+        let clip = Clip::new("shot", "shot.mov", range(0, end), range(0, end));
+        let clip_id = clip.id;
+        track.add_clip(clip).unwrap();
+        timeline.add_track(track);
+        (timeline, track_id, clip_id)
+    }
+
+    /// A timeline whose only clip plays `nested` from `at`.
+    fn edit_of(project: &Project, nested: Uuid, at: i64) -> Timeline {
+        let mut compound = project.compound_clip(nested).unwrap();
+        compound.timeline_range = compound.timeline_range.shifted_by(frames(at));
+        let mut timeline = Timeline::new("edit", 24);
+        let mut track = Track::new("V1", TrackKind::Video);
+        track.add_clip(compound).unwrap();
+        timeline.add_track(track);
+        timeline
+    }
+
+    #[test]
+    fn references_must_exist_and_not_loop() {
+        let mut project = Project::new("film");
+        let (scene, scene_track, _) = scene("scene", 48);
+        let scene_id = project.add_timeline(scene).unwrap();
+        let edit_id = project.add_timeline(edit_of(&project, scene_id, 0)).unwrap();
+
+        let mut orphan = edit_of(&project, scene_id, 0);
+        orphan.tracks[0].clips[0].source = SequenceSource { timeline_id: Uuid::new_v4(), duration: frames(48) }.into();
+        assert!(matches!(project.add_timeline(orphan), Err(TimelineError::TimelineNotFound(_))));
+
+        let back = project.compound_clip(edit_id).unwrap();
+        let result = project.update_timeline(scene_id, |t| {
+            let mut back = back.clone();
+            back.timeline_range = back.timeline_range.shifted_by(frames(48));
+            t.add_clip_to_track(scene_track, back)
+        });
+        assert!(matches!(result, Err(TimelineError::CircularReference(_))));
+        assert_eq!(project.timeline(scene_id).unwrap().tracks[0].clips().len(), 1);
+    }
+
+    #[test]
+    fn clip_at_time_looks_inside_compound_clips() {
+        let mut project = Project::new("film");
+        let (scene, _, shot_id) = scene("scene", 48);
+        let scene_id = project.add_timeline(scene).unwrap();
+        let edit_id = project.add_timeline(edit_of(&project, scene_id, 24)).unwrap();
+
+        let (timeline, clip) = project.clip_at_time(edit_id, &frames(30)).unwrap();
+        assert_eq!(timeline.id, scene_id);
+        assert_eq!(clip.id, shot_id);
+        assert!(project.clip_at_time(edit_id, &frames(10)).is_none());
+        assert!(project.clip_at_time(edit_id, &frames(72)).is_none());
+    }
+
+    #[test]
+    fn nested_length_follows_edits_but_cannot_cut_into_use() {
+        let mut project = Project::new("film");
+        let (scene, scene_track, shot_id) = scene("scene", 48);
+        let scene_id = project.add_timeline(scene).unwrap();
+        let edit_id = project.add_timeline(edit_of(&project, scene_id, 0)).unwrap();
+        let sequence_duration = |project: &Project| match &project.timeline(edit_id).unwrap().tracks[0].clips()[0].source {
+            ClipSource::Sequence(sequence) => sequence.duration,
+            _ => unreachable!(),
+        };
+
+        project
+            .update_timeline(scene_id, |t| t.add_clip_to_track(scene_track, Clip::new("more", "more.mov", range(0, 24), range(48, 72))))
+            .unwrap();
+        assert_eq!(sequence_duration(&project), frames(72));
+
+        // The compound clip still plays frames 0 to 48, so the scene may
+        // shrink back to 48 but no further.
+        let more_id = project.timeline(scene_id).unwrap().tracks[0].clips()[1].id;
+        project.update_timeline(scene_id, |t| t.remove_clip(scene_track, more_id).map(|_| ())).unwrap();
+        assert_eq!(sequence_duration(&project), frames(48));
+        let result = project.update_timeline(scene_id, |t| t.remove_clip(scene_track, shot_id).map(|_| ()));
+        assert!(matches!(result, Err(TimelineError::InsufficientMedia(_))));
+        assert!(project.timeline(scene_id).unwrap().find_clip(shot_id).is_some());
+        assert_eq!(sequence_duration(&project), frames(48));
+    }
+
+    #[test]
+    fn flatten_places_nested_clips_below_their_track() {
+        let mut project = Project::new("film");
+        let (scene, _, _) = scene("scene", 48);
+        let scene_id = project.add_timeline(scene).unwrap();
+        let mut edit = edit_of(&project, scene_id, 24);
+        edit.tracks[0].clips[0].source_range = range(12, 36);
+        edit.tracks[0].clips[0].timeline_range = range(24, 48);
+        let edit_id = project.add_timeline(edit).unwrap();
+
+        let flat = project.flatten(edit_id).unwrap();
+        assert_eq!(flat.tracks.len(), 2);
+        assert!(flat.tracks[0].clips().is_empty());
+        let placed = &flat.tracks[1].clips()[0];
+        assert_eq!(placed.timeline_range, range(24, 48));
+        assert_eq!(placed.source_range, range(12, 36));
+        assert!(project.flatten(Uuid::new_v4()).is_err());
+    }
+
+    #[test]
+    fn flatten_keeps_transitions_on_locked_tracks() {
+        let media = MediaSource::new("shot.mov").with_metadata(MediaMetadata { duration: frames(240), ..MediaMetadata::default() });
+        let mut timeline = Timeline::new("edit", 24);
+        let mut track = Track::new("V1", TrackKind::Video);
+        let first = Clip::new("a", media.clone(), range(48, 144), range(0, 96));
+        let second = Clip::new("b", media, range(48, 144), range(96, 192));
+        let (from, to) = (first.id, second.id);
+        track.add_clip(first).unwrap();
+        track.add_clip(second).unwrap();
+        track.add_transition(from, to, Transition::cross_dissolve(frames(12), frames(6))).unwrap();
+        track.locked = true;
+        timeline.add_track(track);
+        let mut project = Project::new("film");
+        let id = project.add_timeline(timeline).unwrap();
+
+        let flat = project.flatten(id).unwrap();
+        assert!(flat.tracks[0].locked);
+        assert_eq!(flat.tracks[0].clips().len(), 2);
+        assert_eq!(flat.tracks[0].transitions().len(), 1);
+    }
+}
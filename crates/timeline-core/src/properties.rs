@@ -0,0 +1,413 @@
+use serde::{Deserialize, Serialize};
+use crate::effect::{evaluate_keyframes, remove_keyframe, set_keyframe};
+use crate::{Clip, Keyframe, Point, RationalTime, TimelineMetadata};
+
+/// A number that may be keyframed. Serializes as a bare number when it is
+/// constant, matching the TS `Clip.properties` fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "AnimatedRepr", into = "AnimatedRepr")]
+pub struct AnimatedValue {
+    pub value: f64,
+    /// Ordered by time, as offsets from the clip's start.
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AnimatedRepr {
+    Constant(f64),
+    Animated { value: f64, keyframes: Vec<Keyframe> },
+}
+
+impl From<AnimatedRepr> for AnimatedValue {
+    fn from(repr: AnimatedRepr) -> Self {
+        match repr {
+            AnimatedRepr::Constant(value) => Self::new(value),
+            AnimatedRepr::Animated { value, mut keyframes } => {
+                keyframes.sort_by_key(|k| k.time);
+                Self { value, keyframes }
+            }
+        }
+    }
+}
+
+impl From<AnimatedValue> for AnimatedRepr {
+    fn from(animated: AnimatedValue) -> Self {
+        if animated.keyframes.is_empty() {
+            AnimatedRepr::Constant(animated.value)
+        } else {
+            AnimatedRepr::Animated { value: animated.value, keyframes: animated.keyframes }
+        }
+    }
+}
+
+impl AnimatedValue {
+    pub fn new(value: f64) -> Self {
+        Self { value, keyframes: Vec::new() }
+    }
+
+    /// Adds a keyframe, replacing any existing one at the same time.
+    pub fn set_keyframe(&mut self, keyframe: Keyframe) {
+        set_keyframe(&mut self.keyframes, keyframe);
+    }
+
+    pub fn remove_keyframe(&mut self, time: &RationalTime) -> Option<Keyframe> {
+        remove_keyframe(&mut self.keyframes, time)
+    }
+
+    pub fn is_animated(&self) -> bool {
+        !self.keyframes.is_empty()
+    }
+
+    /// The value at `offset` from the clip's start. Keyframes holding
+    /// anything other than a number are ignored.
+    pub fn at(&self, offset: &RationalTime) -> f64 {
+        evaluate_keyframes(&self.keyframes, offset)
+            .and_then(|v| v.as_f64())
+            .unwrap_or(self.value)
+    }
+}
+
+impl From<f64> for AnimatedValue {
+    fn from(value: f64) -> Self {
+        Self::new(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Add,
+}
+
+/// Placement of the clip's picture in the frame. `x`/`y` move the anchor
+/// away from the frame centre in timeline pixels; the anchor is a fraction
+/// of the picture's size, so `0.5, 0.5` is its centre. Rotation is in
+/// degrees clockwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transform {
+    pub x: AnimatedValue,
+    pub y: AnimatedValue,
+    pub scale_x: AnimatedValue,
+    pub scale_y: AnimatedValue,
+    pub rotation: AnimatedValue,
+    pub anchor_x: AnimatedValue,
+    pub anchor_y: AnimatedValue,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            x: 0.0.into(),
+            y: 0.0.into(),
+            scale_x: 1.0.into(),
+            scale_y: 1.0.into(),
+            rotation: 0.0.into(),
+            anchor_x: 0.5.into(),
+            anchor_y: 0.5.into(),
+        }
+    }
+}
+
+/// Amount trimmed from each edge of the picture as a fraction of its size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Crop {
+    pub left: AnimatedValue,
+    pub top: AnimatedValue,
+    pub right: AnimatedValue,
+    pub bottom: AnimatedValue,
+}
+
+impl Crop {
+    /// The visible part of the picture at `offset` as `(x, y, width, height)`
+    /// fractions of the full picture.
+    pub fn rect_at(&self, offset: &RationalTime) -> (f64, f64, f64, f64) {
+        let left = self.left.at(offset).clamp(0.0, 1.0);
+        let top = self.top.at(offset).clamp(0.0, 1.0);
+        let right = self.right.at(offset).clamp(0.0, 1.0 - left);
+        let bottom = self.bottom.at(offset).clamp(0.0, 1.0 - top);
+        (left, top, 1.0 - left - right, 1.0 - top - bottom)
+    }
+}
+
+impl Default for Crop {
+    fn default() -> Self {
+        Self {
+            left: 0.0.into(),
+            top: 0.0.into(),
+            right: 0.0.into(),
+            bottom: 0.0.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipProperties {
+    /// Optional in the TS schema, where a missing value means opaque.
+    #[serde(default = "full_opacity")]
+    pub opacity: AnimatedValue,
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default)]
+    pub crop: Crop,
+}
+
+fn full_opacity() -> AnimatedValue {
+    AnimatedValue::new(1.0)
+}
+
+impl Default for ClipProperties {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0.into(),
+            blend_mode: BlendMode::Normal,
+            transform: Transform::default(),
+            crop: Crop::default(),
+        }
+    }
+}
+
+/// A 2D affine transform in the CSS `matrix(a, b, c, d, e, f)` layout:
+/// `x' = a*x + c*y + e` and `y' = b*x + d*y + f`, with y pointing down.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub fn identity() -> Self {
+        Self { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 }
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Self { e: x, f: y, ..Self::identity() }
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Self { a: x, d: y, ..Self::identity() }
+    }
+
+    /// Clockwise on screen, since y points down.
+    pub fn rotate(degrees: f64) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self { a: cos, b: sin, c: -sin, d: cos, e: 0.0, f: 0.0 }
+    }
+
+    /// `self` followed by `next`.
+    pub fn then(&self, next: &Affine) -> Affine {
+        Affine {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point::new(
+            self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f,
+        )
+    }
+
+    pub fn to_array(&self) -> [f64; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Clip {
+    pub fn opacity_at(&self, time: &RationalTime) -> f64 {
+        self.properties.opacity.at(&(*time - self.timeline_range.start)).clamp(0.0, 1.0)
+    }
+
+    /// The matrix taking pixels of the clip's source picture to timeline
+    /// pixels at timeline time `time`. The picture is first fitted inside
+    /// the frame keeping its aspect ratio, then scaled and rotated about its
+    /// anchor, and the anchor placed at the frame centre plus `x`/`y`.
+    /// Sources without metadata are taken to match the timeline's size.
+    pub fn transform_matrix(&self, time: &RationalTime, timeline: &TimelineMetadata) -> Affine {
+        let offset = *time - self.timeline_range.start;
+        let transform = &self.properties.transform;
+        let (frame_width, frame_height) = (timeline.width as f64, timeline.height as f64);
+        let (width, height) = match self.source.media().and_then(|media| media.metadata.as_ref()) {
+            Some(meta) if meta.width > 0 && meta.height > 0 => (meta.width as f64, meta.height as f64),
+            _ => (frame_width, frame_height),
+        };
+        let fit = (frame_width / width).min(frame_height / height);
+
+        Affine::translate(-transform.anchor_x.at(&offset) * width, -transform.anchor_y.at(&offset) * height)
+            .then(&Affine::scale(fit * transform.scale_x.at(&offset), fit * transform.scale_y.at(&offset)))
+            .then(&Affine::rotate(transform.rotation.at(&offset)))
+            .then(&Affine::translate(
+                frame_width / 2.0 + transform.x.at(&offset),
+                frame_height / 2.0 + transform.y.at(&offset),
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::{AudioProperties, Interpolation, MediaMetadata, MediaSource, TimeRange};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn clip(metadata: Option<MediaMetadata>) -> Clip {
+        let mut media = MediaSource::new("shot.mov");
+        if let Some(metadata) = metadata {
+            media = media.with_metadata(metadata);
+        }
+        let range = TimeRange::new(frames(0), frames(48));
+        Clip::new("shot", media, range, range)
+    }
+
+    fn assert_maps(matrix: &Affine, from: (f64, f64), to: (f64, f64)) {
+        let point = matrix.apply(Point::new(from.0, from.1));
+        assert!((point.x - to.0).abs() < 1e-9 && (point.y - to.1).abs() < 1e-9, "{from:?} went to {point:?}, not {to:?}");
+    }
+
+    #[test]
+    fn properties_without_opacity_are_opaque() {
+        let properties: ClipProperties = serde_json::from_value(json!({ "volume": 1 })).unwrap();
+        assert_eq!(properties, ClipProperties::default());
+    }
+
+    #[test]
+    fn properties_round_trip_the_ts_shape() {
+        let ts = json!({
+            "opacity": 0.5,
+            "transform": { "x": 10.0, "y": -4.0, "scaleX": 2.0, "scaleY": 2.0, "rotation": 90.0, "anchorX": 0.5, "anchorY": 0.5 },
+            "crop": { "left": 0.1, "top": 0.0, "right": 0.0, "bottom": 0.2 }
+        });
+        let properties: ClipProperties = serde_json::from_value(ts.clone()).unwrap();
+        assert_eq!(properties.opacity, AnimatedValue::new(0.5));
+        assert_eq!(properties.transform.scale_x, AnimatedValue::new(2.0));
+        assert_eq!(properties.crop.bottom, AnimatedValue::new(0.2));
+
+        let mut written = serde_json::to_value(&properties).unwrap();
+        assert_eq!(written["blendMode"], json!("normal"));
+        written.as_object_mut().unwrap().remove("blendMode");
+        assert_eq!(written, ts);
+    }
+
+    #[test]
+    fn untransformed_pictures_fill_the_frame() {
+        let timeline = TimelineMetadata::default();
+        let matrix = clip(None).transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (0.0, 0.0), (0.0, 0.0));
+        assert_maps(&matrix, (1920.0, 1080.0), (1920.0, 1080.0));
+
+        let uhd = MediaMetadata { width: 3840, height: 2160, ..MediaMetadata::default() };
+        let matrix = clip(Some(uhd)).transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (3840.0, 2160.0), (1920.0, 1080.0));
+
+        let square = MediaMetadata { width: 1080, height: 1080, ..MediaMetadata::default() };
+        let matrix = clip(Some(square)).transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (0.0, 0.0), (420.0, 0.0));
+    }
+
+    #[test]
+    fn pictures_scale_and_rotate_about_their_anchor() {
+        let timeline = TimelineMetadata::default();
+        let mut clip = clip(None);
+        clip.properties.transform.scale_x = 2.0.into();
+        clip.properties.transform.scale_y = 2.0.into();
+        let matrix = clip.transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (960.0, 540.0), (960.0, 540.0));
+        assert_maps(&matrix, (0.0, 0.0), (-960.0, -540.0));
+
+        clip.properties.transform.scale_x = 1.0.into();
+        clip.properties.transform.scale_y = 1.0.into();
+        clip.properties.transform.rotation = 90.0.into();
+        let matrix = clip.transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (1060.0, 540.0), (960.0, 640.0));
+
+        clip.properties.transform.anchor_x = 0.0.into();
+        clip.properties.transform.anchor_y = 0.0.into();
+        clip.properties.transform.x = 100.0.into();
+        let matrix = clip.transform_matrix(&frames(0), &timeline);
+        assert_maps(&matrix, (0.0, 0.0), (1060.0, 540.0));
+        assert_maps(&matrix, (10.0, 0.0), (1060.0, 550.0));
+    }
+
+    #[test]
+    fn transforms_follow_their_keyframes() {
+        let timeline = TimelineMetadata::default();
+        let mut clip = clip(None).with_timeline_range(TimeRange::new(frames(100), frames(48)));
+        let mut x = AnimatedValue::new(0.0);
+        x.set_keyframe(Keyframe::new(frames(0), 0.0, Interpolation::Linear));
+        x.set_keyframe(Keyframe::new(frames(24), 240.0, Interpolation::Linear));
+        clip.properties.transform.x = x;
+        assert_maps(&clip.transform_matrix(&frames(112), &timeline), (960.0, 540.0), (1080.0, 540.0));
+        assert_maps(&clip.transform_matrix(&frames(140), &timeline), (960.0, 540.0), (1200.0, 540.0));
+    }
+
+    /// A saved clip with its `properties` replaced by `properties` and the
+    /// retime and audio fields only TS-shaped clips leave out removed.
+    fn ts_clip(properties: serde_json::Value) -> Result<Clip, serde_json::Error> {
+        let mut saved = serde_json::to_value(clip(None)).unwrap();
+        let fields = saved.as_object_mut().unwrap();
+        for key in ["speed", "reversed", "audio"] {
+            fields.remove(key);
+        }
+        fields.insert("properties".into(), properties);
+        serde_json::from_value(saved)
+    }
+
+    #[test]
+    fn ts_retime_and_volume_properties_are_applied() {
+        let clip = ts_clip(json!({ "opacity": 0.5, "speed": 2.0, "isReversed": true, "volume": 0.5 })).unwrap();
+        assert_eq!(clip.properties.opacity, AnimatedValue::new(0.5));
+        assert_eq!(clip.speed, 2.0);
+        assert!(clip.reversed);
+        assert!((clip.audio.gain_db - -6.0206).abs() < 1e-3);
+
+        let muted = ts_clip(json!({ "volume": 0 })).unwrap();
+        assert!(muted.audio.gain_db.is_finite());
+        assert!(ts_clip(json!({ "speed": 0 })).is_err());
+        assert!(ts_clip(json!({ "volume": -1 })).is_err());
+    }
+
+    #[test]
+    fn clip_fields_win_over_ts_properties() {
+        let mut saved = serde_json::to_value(clip(None).with_speed(0.5)).unwrap();
+        saved["properties"]["speed"] = json!(2.0);
+        saved["properties"]["volume"] = json!(0.0);
+        let clip: Clip = serde_json::from_value(saved).unwrap();
+        assert_eq!(clip.speed, 0.5);
+        assert_eq!(clip.audio, AudioProperties::default());
+    }
+}
@@ -1,55 +1,409 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Mul, Neg, Sub};
 use serde::{Deserialize, Serialize};
+use crate::{FrameRate, TimelineError};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoundingMode {
+    Floor,
+    Ceil,
+    Nearest,
+}
+
+/// A frame count at a given rate. Arithmetic and comparisons are exact:
+/// operands with different rates are brought onto their least common rate
+/// instead of being rounded through `f64`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RationalTime {
     pub value: i64,
-    pub rate: u32,
+    pub rate: FrameRate,
 }
 
 impl RationalTime {
-    pub fn new(value: i64, rate: u32) -> Self {
-        Self { value, rate }
+    /// Panics on a zero rate; use [`RationalTime::try_new`] for rates that
+    /// did not come from this crate.
+    pub fn new(value: i64, rate: impl Into<FrameRate>) -> Self {
+        Self::try_new(value, rate).unwrap_or_else(|err| panic!("{}", err))
     }
 
-    pub fn from_seconds(seconds: f64, rate: u32) -> Self {
-        let value = (seconds * rate as f64).round() as i64;
+    pub fn try_new(value: i64, rate: impl Into<FrameRate>) -> Result<Self, TimelineError> {
+        let rate = rate.into();
+        rate.validate()?;
+        Ok(Self { value, rate })
+    }
+
+    pub fn from_seconds(seconds: f64, rate: impl Into<FrameRate>) -> Self {
+        let rate = rate.into();
+        let value = (seconds * rate.as_f64()).round() as i64;
         Self { value, rate }
     }
 
+    /// Like [`RationalTime::from_seconds`], but fails on a zero rate and on
+    /// seconds that are not finite or whose frame count does not fit an
+    /// `i64`, instead of saturating.
+    pub fn checked_from_seconds(seconds: f64, rate: impl Into<FrameRate>) -> Result<Self, TimelineError> {
+        let rate = rate.into();
+        rate.validate()?;
+        let value = (seconds * rate.as_f64()).round();
+        // `i64::MAX as f64` rounds up to 2^63, which is itself out of range.
+        if !value.is_finite() || value < i64::MIN as f64 || value >= i64::MAX as f64 {
+            return Err(TimelineError::OperationFailed(format!("{} seconds is out of range at {}", seconds, rate)));
+        }
+        Ok(Self { value: value as i64, rate })
+    }
+
     pub fn to_seconds(&self) -> f64 {
-        self.value as f64 / self.rate as f64
+        self.value as f64 * self.rate.denominator as f64 / self.rate.numerator as f64
     }
 
     pub fn to_frames(&self) -> i64 {
         self.value
     }
 
-    pub fn rescaled(&self, new_rate: u32) -> Self {
-        let new_value = (self.value as f64 * new_rate as f64 / self.rate as f64).round() as i64;
-        Self { value: new_value, rate: new_rate }
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { value: self.value.abs(), rate: self.rate }
     }
 
-    pub fn add(&self, other: &RationalTime) -> Self {
+    pub fn rescaled(&self, new_rate: impl Into<FrameRate>) -> Self {
+        self.rescaled_with(new_rate, RoundingMode::Nearest)
+    }
+
+    pub fn rescaled_with(&self, new_rate: impl Into<FrameRate>, rounding: RoundingMode) -> Self {
+        let new_rate = new_rate.into();
+        let numerator = self.value as i128
+            * self.rate.denominator as i128
+            * new_rate.numerator as i128;
+        let denominator = self.rate.numerator as i128 * new_rate.denominator as i128;
+        let value = div_round(numerator, denominator, rounding);
+        Self { value: value as i64, rate: new_rate }
+    }
+
+    /// Whether this time falls exactly on a frame boundary of `rate`.
+    pub fn is_on_grid(&self, rate: impl Into<FrameRate>) -> bool {
+        let rate = rate.into();
+        let numerator = self.value as i128 * self.rate.denominator as i128 * rate.numerator as i128;
+        numerator % (self.rate.numerator as i128 * rate.denominator as i128) == 0
+    }
+
+    /// Both times at the coarsest rate that holds each of them exactly.
+    /// Fails when that rate does not fit a `u32` frame rate.
+    pub fn with_common_rate(&self, other: &RationalTime) -> Result<(Self, Self), TimelineError> {
         if self.rate == other.rate {
-            Self { value: self.value + other.value, rate: self.rate }
-        } else {
-            let rescaled = self.rescaled(other.rate);
-            Self { value: rescaled.value + other.value, rate: other.rate }
+            return Ok((*self, *other));
         }
+        let numerator = lcm(self.rate.numerator as u128, other.rate.numerator as u128);
+        let denominator = gcd(self.rate.denominator as u128, other.rate.denominator as u128);
+        let numerator = u32::try_from(numerator)
+            .map_err(|_| TimelineError::IncompatibleRates(self.rate, other.rate))?;
+        let rate = FrameRate::new(numerator, denominator as u32);
+        Ok((self.exact_at(rate)?, other.exact_at(rate)?))
     }
 
-    pub fn subtract(&self, other: &RationalTime) -> Self {
-        if self.rate == other.rate {
-            Self { value: self.value - other.value, rate: self.rate }
-        } else {
-            let rescaled = self.rescaled(other.rate);
-            Self { value: rescaled.value - other.value, rate: other.rate }
+    /// `self + other`, falling back to the reduced fraction of the exact
+    /// sum when the operands share no `u32` rate.
+    pub fn checked_add(&self, other: &RationalTime) -> Result<Self, TimelineError> {
+        self.combine(other, 1)
+    }
+
+    pub fn checked_sub(&self, other: &RationalTime) -> Result<Self, TimelineError> {
+        self.combine(other, -1)
+    }
+
+    pub fn checked_mul(&self, factor: i64) -> Result<Self, TimelineError> {
+        let value = self.value.checked_mul(factor).ok_or_else(|| {
+            TimelineError::OperationFailed(format!("{} frames times {} overflows at {}", self.value, factor, self.rate))
+        })?;
+        Ok(Self { value, rate: self.rate })
+    }
+
+    /// `self * numerator / denominator` at `rate`, rounded to the nearest
+    /// frame of `rate` from the exact product.
+    pub fn scaled(&self, numerator: i64, denominator: i64, rate: impl Into<FrameRate>) -> Self {
+        let rate = rate.into();
+        let scaled = self.value as i128 * numerator as i128 * self.rate.denominator as i128 * rate.numerator as i128;
+        let divisor = denominator as i128 * self.rate.numerator as i128 * rate.denominator as i128;
+        let (scaled, divisor) = if divisor < 0 { (-scaled, -divisor) } else { (scaled, divisor) };
+        let value = div_round(scaled, divisor, RoundingMode::Nearest);
+        Self { value: value as i64, rate }
+    }
+
+    /// `self / other` as a plain number, from exact frame counts.
+    pub fn ratio_to(&self, other: &RationalTime) -> f64 {
+        let numerator = self.value as i128 * self.rate.denominator as i128 * other.rate.numerator as i128;
+        let denominator = other.value as i128 * other.rate.denominator as i128 * self.rate.numerator as i128;
+        numerator as f64 / denominator as f64
+    }
+
+    fn combine(&self, other: &RationalTime, sign: i64) -> Result<Self, TimelineError> {
+        let overflow = || TimelineError::OperationFailed(format!("time arithmetic overflowed at {} and {}", self.rate, other.rate));
+        if let Ok((lhs, rhs)) = self.with_common_rate(other) {
+            let value = rhs.value.checked_mul(sign).and_then(|rhs| lhs.value.checked_add(rhs)).ok_or_else(overflow)?;
+            return Ok(Self { value, rate: lhs.rate });
         }
+
+        // Seconds as `value * den / num`; the sum over the product of both
+        // numerators is exact and may reduce back into range.
+        let (a, b) = (self.rate, other.rate);
+        let lhs = (self.value as i128).checked_mul(a.denominator as i128 * b.numerator as i128);
+        let rhs = (other.value as i128 * sign as i128).checked_mul(b.denominator as i128 * a.numerator as i128);
+        let numerator = lhs.zip(rhs).and_then(|(lhs, rhs)| lhs.checked_add(rhs)).ok_or_else(overflow)?;
+        let denominator = a.numerator as i128 * b.numerator as i128;
+        let divisor = gcd(numerator.unsigned_abs(), denominator as u128).max(1) as i128;
+        let rate = u32::try_from(denominator / divisor).map_err(|_| TimelineError::IncompatibleRates(a, b))?;
+        let value = i64::try_from(numerator / divisor).map_err(|_| overflow())?;
+        Ok(Self { value, rate: FrameRate::new(rate, 1) })
+    }
+
+    fn exact_at(&self, rate: FrameRate) -> Result<Self, TimelineError> {
+        let factor = (rate.numerator as i128 * self.rate.denominator as i128)
+            / (rate.denominator as i128 * self.rate.numerator as i128);
+        let value = i64::try_from(self.value as i128 * factor)
+            .map_err(|_| TimelineError::OperationFailed(format!("{} frames overflow at {}", self.value, rate)))?;
+        Ok(Self { value, rate })
+    }
+
+    fn reduced(&self) -> (i128, i128) {
+        let numerator = self.value as i128 * self.rate.denominator as i128;
+        let denominator = self.rate.numerator as i128;
+        let divisor = gcd(numerator.unsigned_abs(), denominator as u128).max(1) as i128;
+        (numerator / divisor, denominator / divisor)
     }
 }
 
 impl Default for RationalTime {
     fn default() -> Self {
-        Self { value: 0, rate: 24 }
+        Self { value: 0, rate: FrameRate::FPS_24 }
+    }
+}
+
+impl PartialEq for RationalTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RationalTime {}
+
+impl Hash for RationalTime {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.reduced().hash(state);
+    }
+}
+
+impl PartialOrd for RationalTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RationalTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = self.value as i128 * self.rate.denominator as i128 * other.rate.numerator as i128;
+        let rhs = other.value as i128 * other.rate.denominator as i128 * self.rate.numerator as i128;
+        lhs.cmp(&rhs)
+    }
+}
+
+/// Panics when the result overflows or no frame rate can hold it exactly;
+/// use [`RationalTime::checked_add`] where that can happen, such as on
+/// times that came from JS.
+impl Add for RationalTime {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(&other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+impl Sub for RationalTime {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(&other).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+impl Neg for RationalTime {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self { value: -self.value, rate: self.rate }
+    }
+}
+
+/// Panics on overflow; see [`RationalTime::checked_mul`].
+impl Mul<i64> for RationalTime {
+    type Output = Self;
+
+    fn mul(self, factor: i64) -> Self {
+        self.checked_mul(factor).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+fn div_round(numerator: i128, denominator: i128, rounding: RoundingMode) -> i128 {
+    let quotient = numerator.div_euclid(denominator);
+    let remainder = numerator.rem_euclid(denominator);
+    if remainder == 0 {
+        return quotient;
+    }
+    match rounding {
+        RoundingMode::Floor => quotient,
+        RoundingMode::Ceil => quotient + 1,
+        RoundingMode::Nearest => {
+            if remainder * 2 >= denominator { quotient + 1 } else { quotient }
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+fn lcm(a: u128, b: u128) -> u128 {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: [FrameRate; 5] = [
+        FrameRate::FPS_24,
+        FrameRate::FPS_25,
+        FrameRate::FPS_29_97,
+        FrameRate { numerator: 48000, denominator: 1 },
+        FrameRate { numerator: 44100, denominator: 1 },
+    ];
+
+    /// Small deterministic generator so the property checks are repeatable.
+    fn values(seed: u64) -> impl Iterator<Item = i64> {
+        let mut state = seed;
+        std::iter::repeat_with(move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) as i64 % 2_000_000) - 1_000_000
+        })
+    }
+
+    #[test]
+    fn addition_is_associative_across_rates() {
+        let mut values = values(7);
+        for &ra in &RATES {
+            for &rb in &RATES {
+                for &rc in &RATES {
+                    for _ in 0..20 {
+                        let a = RationalTime::new(values.next().unwrap(), ra);
+                        let b = RationalTime::new(values.next().unwrap(), rb);
+                        let c = RationalTime::new(values.next().unwrap(), rc);
+                        assert_eq!((a + b) + c, a + (b + c));
+                        assert_eq!((a + b) - b, a);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_mixed_rate_accumulation_does_not_drift() {
+        let frame_24 = RationalTime::new(1, 24);
+        let frame_30 = RationalTime::new(1, 30);
+        let mut total = RationalTime::new(0, 24);
+        // Each step is 1/24 + 1/30 = 3/40 of a second, which no f64 sum of
+        // the two holds exactly.
+        for _ in 0..40 * 1000 {
+            total = total + frame_24 + frame_30;
+        }
+        assert_eq!(total, RationalTime::new(3000, 1));
+        assert_eq!(total.rescaled(24).value, 3000 * 24);
+        assert_eq!(total.rescaled(30).value, 3000 * 30);
+    }
+
+    #[test]
+    fn mixed_ntsc_accumulation_stays_exact() {
+        let mut total = RationalTime::new(0, FrameRate::FPS_29_97);
+        for _ in 0..30000 {
+            total = total + RationalTime::new(1, FrameRate::FPS_29_97) + RationalTime::new(1, 48000);
+        }
+        assert_eq!(total, RationalTime::new(1001, 1) + RationalTime::new(30000, 48000));
+    }
+
+    #[test]
+    fn rescaling_negative_values_rounds_by_mode() {
+        // -5 frames at 24fps is -6.25 frames at 30fps.
+        let time = RationalTime::new(-5, 24);
+        assert_eq!(time.rescaled_with(30, RoundingMode::Floor).value, -7);
+        assert_eq!(time.rescaled_with(30, RoundingMode::Ceil).value, -6);
+        assert_eq!(time.rescaled_with(30, RoundingMode::Nearest).value, -6);
+
+        // -1 frame at 24fps is -1.25 at 30fps; -3 is -3.75.
+        assert_eq!(RationalTime::new(-1, 24).rescaled_with(30, RoundingMode::Nearest).value, -1);
+        assert_eq!(RationalTime::new(-3, 24).rescaled_with(30, RoundingMode::Nearest).value, -4);
+        // Halves round up: -2 frames at 24fps is -2.5 at 30fps.
+        assert_eq!(RationalTime::new(-2, 24).rescaled_with(30, RoundingMode::Nearest).value, -2);
+        assert_eq!(RationalTime::new(-2, 24).rescaled_with(30, RoundingMode::Floor).value, -3);
+        assert_eq!(RationalTime::new(-4, 24).rescaled_with(30, RoundingMode::Ceil).value, -5);
+    }
+
+    #[test]
+    fn sums_that_outgrow_a_common_rate_are_not_truncated() {
+        let one = |rate: FrameRate| RationalTime::new(1, rate);
+        let partial = one(FrameRate::new(96000, 1)) + one(FrameRate::new(44100, 1)) + one(FrameRate::FPS_23_976) + one(FrameRate::new(23, 1));
+        // 96000, 44100, 24000/1001, 23 and 29 share no u32 rate, but the
+        // exact sum reduces into one.
+        let sum = partial.checked_add(&one(FrameRate::new(29, 1))).unwrap();
+        let expected = 1.0 / 96000.0 + 1.0 / 44100.0 + 1001.0 / 24000.0 + 1.0 / 23.0 + 1.0 / 29.0;
+        assert!((sum.to_seconds() - expected).abs() < 1e-12, "got {}", sum.to_seconds());
+        assert_eq!(sum - one(FrameRate::new(29, 1)), partial);
+
+        let far_apart = RationalTime::new(1, FrameRate::new(4_000_000_007, 1));
+        let result = far_apart.checked_add(&RationalTime::new(1, FrameRate::new(4_000_000_009, 1)));
+        assert!(matches!(result, Err(TimelineError::IncompatibleRates(..))), "got {:?}", result);
+    }
+
+    #[test]
+    fn fallback_keeps_sums_that_reduce_into_range() {
+        let a = RationalTime::new(1, FrameRate::new(4_000_000_007, 1));
+        let b = RationalTime::new(-1, FrameRate::new(4_000_000_007, 1));
+        assert!(a.checked_add(&b).unwrap().is_zero());
+        let c = RationalTime::new(4_000_000_007, FrameRate::new(4_000_000_007, 1));
+        let d = RationalTime::new(3, FrameRate::new(3, 1));
+        assert_eq!(c.checked_add(&d).unwrap(), RationalTime::new(2, 1));
+    }
+
+    #[test]
+    fn zero_rates_are_rejected() {
+        assert!(matches!(RationalTime::try_new(5, 0u32), Err(TimelineError::InvalidFrameRate(_))));
+        assert!(matches!(RationalTime::checked_from_seconds(1.0, 0u32), Err(TimelineError::InvalidFrameRate(_))));
+        assert!(std::panic::catch_unwind(|| RationalTime::new(5, 0u32)).is_err());
+        assert!(serde_json::from_str::<RationalTime>(r#"{ "value": 5, "rate": 0 }"#).is_err());
+    }
+
+    #[test]
+    fn checked_arithmetic_reports_overflow() {
+        let big = RationalTime::new(i64::MAX - 1, 24);
+        assert!(matches!(big.checked_add(&RationalTime::new(2, 24)), Err(TimelineError::OperationFailed(_))));
+        assert!(matches!(RationalTime::new(i64::MIN + 1, 24).checked_sub(&RationalTime::new(2, 24)), Err(TimelineError::OperationFailed(_))));
+        assert!(matches!(big.checked_mul(2), Err(TimelineError::OperationFailed(_))));
+        assert_eq!(RationalTime::new(12, 24).checked_mul(-3).unwrap(), RationalTime::new(-36, 24));
+        assert_eq!(RationalTime::new(12, 24) * 3, RationalTime::new(36, 24));
+        assert!(std::panic::catch_unwind(|| big * 2).is_err());
+    }
+
+    #[test]
+    fn checked_seconds_reject_what_would_saturate() {
+        assert_eq!(RationalTime::checked_from_seconds(1.5, 24).unwrap(), RationalTime::new(36, 24));
+        assert_eq!(RationalTime::checked_from_seconds(-1.5, 24).unwrap(), RationalTime::new(-36, 24));
+        for seconds in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e18, -1e18] {
+            assert!(RationalTime::checked_from_seconds(seconds, 24).is_err(), "{}", seconds);
+        }
     }
 }
@@ -0,0 +1,383 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{FrameRate, Marker, RationalTime, Timeline};
+
+/// What a snap target marks. Names match the web app's snap guides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnapSource {
+    Playhead,
+    InPoint,
+    OutPoint,
+    Marker,
+    ClipStart,
+    ClipEnd,
+    TransitionStart,
+    TransitionEnd,
+}
+
+impl SnapSource {
+    /// Higher wins when several targets are within reach.
+    pub fn default_priority(&self) -> i32 {
+        match self {
+            SnapSource::Playhead => 3,
+            SnapSource::InPoint | SnapSource::OutPoint | SnapSource::Marker => 2,
+            SnapSource::ClipStart | SnapSource::ClipEnd => 1,
+            SnapSource::TransitionStart | SnapSource::TransitionEnd => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapTarget {
+    pub time: RationalTime,
+    pub source: SnapSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<Uuid>,
+    /// The clip, marker or transition the target belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<Uuid>,
+}
+
+/// How far from a target a dragged time still snaps. Pixel tolerances
+/// follow the current zoom.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "unit", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SnapTolerance {
+    Frames { frames: u32 },
+    Pixels { pixels: f64, pixels_per_second: f64 },
+}
+
+impl SnapTolerance {
+    pub fn to_time(&self, rate: FrameRate) -> RationalTime {
+        match *self {
+            SnapTolerance::Frames { frames } => RationalTime::new(frames as i64, rate),
+            SnapTolerance::Pixels { pixels, pixels_per_second } if pixels_per_second > 0.0 => {
+                RationalTime::from_seconds((pixels / pixels_per_second).max(0.0), rate)
+            }
+            SnapTolerance::Pixels { .. } => RationalTime::new(0, rate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapOptions {
+    pub tolerance: SnapTolerance,
+    #[serde(default)]
+    pub playhead: Option<RationalTime>,
+    #[serde(default)]
+    pub in_point: Option<RationalTime>,
+    #[serde(default)]
+    pub out_point: Option<RationalTime>,
+    /// Clips being dragged; they and their markers and transitions are
+    /// not targets.
+    #[serde(default)]
+    pub exclude_clip_ids: Vec<Uuid>,
+    /// Overrides [`SnapSource::default_priority`].
+    #[serde(default)]
+    pub priorities: HashMap<SnapSource, i32>,
+}
+
+impl SnapOptions {
+    pub fn new(tolerance: SnapTolerance) -> Self {
+        Self {
+            tolerance,
+            playhead: None,
+            in_point: None,
+            out_point: None,
+            exclude_clip_ids: Vec::new(),
+            priorities: HashMap::new(),
+        }
+    }
+
+    pub fn with_playhead(mut self, time: RationalTime) -> Self {
+        self.playhead = Some(time);
+        self
+    }
+
+    pub fn with_in_out(mut self, in_point: Option<RationalTime>, out_point: Option<RationalTime>) -> Self {
+        self.in_point = in_point;
+        self.out_point = out_point;
+        self
+    }
+
+    pub fn excluding(mut self, clip_ids: impl IntoIterator<Item = Uuid>) -> Self {
+        self.exclude_clip_ids.extend(clip_ids);
+        self
+    }
+
+    pub fn with_priority(mut self, source: SnapSource, priority: i32) -> Self {
+        self.priorities.insert(source, priority);
+        self
+    }
+
+    pub fn priority(&self, source: SnapSource) -> i32 {
+        self.priorities.get(&source).copied().unwrap_or_else(|| source.default_priority())
+    }
+}
+
+impl Timeline {
+    /// Everything a drag can snap to, in no particular order. Hidden
+    /// tracks offer nothing.
+    pub fn snap_targets(&self, options: &SnapOptions) -> Vec<SnapTarget> {
+        let excluded = |id: Option<Uuid>| id.is_some_and(|id| options.exclude_clip_ids.contains(&id));
+        let marker_target = |marker: &Marker, track_id: Option<Uuid>| SnapTarget {
+            time: marker.time,
+            source: SnapSource::Marker,
+            track_id,
+            item_id: Some(marker.id),
+        };
+
+        let mut targets = Vec::new();
+        let fixed = [
+            (options.playhead, SnapSource::Playhead),
+            (options.in_point, SnapSource::InPoint),
+            (options.out_point, SnapSource::OutPoint),
+        ];
+        for (time, source) in fixed {
+            if let Some(time) = time {
+                targets.push(SnapTarget { time, source, track_id: None, item_id: None });
+            }
+        }
+        targets.extend(self.markers.iter().map(|m| marker_target(m, None)));
+
+        for track in self.tracks.iter().filter(|t| t.enabled) {
+            let track_id = Some(track.id);
+            targets.extend(track.markers.iter().map(|m| marker_target(m, track_id)));
+            for clip in track.clips.iter().filter(|c| !excluded(Some(c.id))) {
+                let edges = [
+                    (clip.timeline_range.start, SnapSource::ClipStart),
+                    (clip.timeline_range.end(), SnapSource::ClipEnd),
+                ];
+                for (time, source) in edges {
+                    targets.push(SnapTarget { time, source, track_id, item_id: Some(clip.id) });
+                }
+                targets.extend(clip.timeline_markers().map(|m| marker_target(&m, track_id)));
+            }
+            for transition in &track.transitions {
+                if excluded(transition.from_clip_id) || excluded(transition.to_clip_id) {
+                    continue;
+                }
+                let Some(edit_point) = transition.to_clip_id.and_then(|id| track.clip(id)).map(|c| c.timeline_range.start) else {
+                    continue;
+                };
+                let edges = [
+                    (edit_point - transition.in_point, SnapSource::TransitionStart),
+                    (edit_point + transition.out_point(), SnapSource::TransitionEnd),
+                ];
+                for (time, source) in edges {
+                    targets.push(SnapTarget { time, source, track_id, item_id: Some(transition.id) });
+                }
+            }
+        }
+        targets
+    }
+
+    /// Collects the targets for `options` once, for a drag that will snap
+    /// many times against an unchanged timeline.
+    pub fn snap_context(&self, options: &SnapOptions) -> SnapContext {
+        SnapContext {
+            targets: self
+                .snap_targets(options)
+                .into_iter()
+                .map(|target| (options.priority(target.source), target))
+                .collect(),
+            tolerance: options.tolerance.to_time(self.metadata.frame_rate),
+            frame_rate: self.metadata.frame_rate,
+        }
+    }
+
+    /// The target `time` should snap to: the highest priority one within
+    /// the tolerance, and of those the nearest.
+    pub fn snap(&self, time: &RationalTime, options: &SnapOptions) -> Option<SnapTarget> {
+        self.snap_context(options).snap(time).cloned()
+    }
+}
+
+/// A timeline's snap targets with their priorities, built by
+/// [`Timeline::snap_context`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapContext {
+    targets: Vec<(i32, SnapTarget)>,
+    tolerance: RationalTime,
+    frame_rate: FrameRate,
+}
+
+impl SnapContext {
+    pub fn targets(&self) -> impl Iterator<Item = &SnapTarget> {
+        self.targets.iter().map(|(_, target)| target)
+    }
+
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// The highest priority target within the tolerance of `time`, and of
+    /// those the nearest. Exact ties go to the target collected first:
+    /// playhead, in and out points, timeline markers, then tracks from the
+    /// top.
+    pub fn snap(&self, time: &RationalTime) -> Option<&SnapTarget> {
+        self.targets
+            .iter()
+            .filter_map(|(priority, target)| Some((*priority, target.time.checked_sub(time).ok()?.abs(), target)))
+            .filter(|(_, distance, _)| *distance <= self.tolerance)
+            .min_by_key(|(priority, distance, _)| (Reverse(*priority), *distance))
+            .map(|(_, _, target)| target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Clip, MediaMetadata, MediaSource, TimeRange, Track, TrackKind, Transition};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// Clips at 0..96 and 96..192 joined by a transition over 84..108, a
+    /// clip marker at 148, a track marker at 170 and a timeline marker at
+    /// 200.
+    fn timeline() -> (Timeline, Uuid, [Uuid; 2]) {
+        let media = MediaSource::new("shot.mov").with_metadata(MediaMetadata { duration: frames(192), ..MediaMetadata::default() });
+        let mut track = Track::new("V1", TrackKind::Video);
+        let a = Clip::new("a", media.clone(), range(48, 144), range(0, 96));
+        let mut b = Clip::new("b", media, range(48, 144), range(96, 192));
+        b.add_marker(Marker::new("clip", frames(100)));
+        let ids = [a.id, b.id];
+        track.add_clip(a).unwrap();
+        track.add_clip(b).unwrap();
+        track.add_transition(ids[0], ids[1], Transition::cross_dissolve(frames(24), frames(12))).unwrap();
+        track.add_marker(Marker::new("track", frames(170)));
+        let track_id = track.id;
+
+        let mut timeline = Timeline::new("edit", 24);
+        timeline.add_track(track);
+        timeline.add_marker(Marker::new("timeline", frames(200)));
+        (timeline, track_id, ids)
+    }
+
+    fn options(tolerance: u32) -> SnapOptions {
+        SnapOptions::new(SnapTolerance::Frames { frames: tolerance })
+            .with_playhead(frames(220))
+            .with_in_out(Some(frames(230)), Some(frames(240)))
+    }
+
+    fn kinds(targets: &[SnapTarget]) -> Vec<(SnapSource, i64)> {
+        let mut kinds: Vec<_> = targets.iter().map(|t| (t.source, t.time.value)).collect();
+        kinds.sort_by_key(|(source, time)| (*time, source.default_priority()));
+        kinds
+    }
+
+    #[test]
+    fn every_kind_of_target_is_offered() {
+        let (timeline, track_id, [a, b]) = timeline();
+        let targets = timeline.snap_targets(&options(0));
+        assert_eq!(
+            kinds(&targets),
+            vec![
+                (SnapSource::ClipStart, 0),
+                (SnapSource::TransitionStart, 84),
+                (SnapSource::ClipEnd, 96),
+                (SnapSource::ClipStart, 96),
+                (SnapSource::TransitionEnd, 108),
+                (SnapSource::Marker, 148),
+                (SnapSource::Marker, 170),
+                (SnapSource::ClipEnd, 192),
+                (SnapSource::Marker, 200),
+                (SnapSource::Playhead, 220),
+                (SnapSource::InPoint, 230),
+                (SnapSource::OutPoint, 240),
+            ]
+        );
+        let clip_start = targets.iter().find(|t| t.source == SnapSource::ClipStart && t.time == frames(96)).unwrap();
+        assert_eq!((clip_start.track_id, clip_start.item_id), (Some(track_id), Some(b)));
+        assert!(targets.iter().any(|t| t.source == SnapSource::ClipEnd && t.item_id == Some(a)));
+        assert!(targets.iter().any(|t| t.source == SnapSource::Marker && t.track_id.is_none()));
+    }
+
+    #[test]
+    fn dragged_and_hidden_clips_offer_nothing() {
+        let (mut timeline, track_id, [a, b]) = timeline();
+        let targets = timeline.snap_targets(&options(0).excluding([b]));
+        assert_eq!(
+            kinds(&targets),
+            vec![
+                (SnapSource::ClipStart, 0),
+                (SnapSource::ClipEnd, 96),
+                (SnapSource::Marker, 170),
+                (SnapSource::Marker, 200),
+                (SnapSource::Playhead, 220),
+                (SnapSource::InPoint, 230),
+                (SnapSource::OutPoint, 240),
+            ]
+        );
+        assert!(targets.iter().all(|t| t.item_id != Some(b)));
+        assert!(targets.iter().any(|t| t.item_id == Some(a)));
+
+        timeline.track_mut(track_id).unwrap().enabled = false;
+        assert!(timeline.snap_targets(&options(0)).iter().all(|t| t.track_id.is_none()));
+    }
+
+    #[test]
+    fn priority_beats_distance() {
+        let (timeline, _, _) = timeline();
+        let options = options(5).with_playhead(frames(99));
+        // The clip edge at 96 is nearer, but the playhead ranks higher.
+        assert_eq!(timeline.snap(&frames(97), &options).unwrap().source, SnapSource::Playhead);
+        // The transition end at 108 is within reach of 104 but ranks lowest.
+        assert_eq!(timeline.snap(&frames(104), &options).unwrap().source, SnapSource::Playhead);
+
+        let options = options.with_priority(SnapSource::ClipEnd, 10);
+        assert_eq!(timeline.snap(&frames(97), &options).unwrap().source, SnapSource::ClipEnd);
+    }
+
+    #[test]
+    fn ties_go_to_the_target_collected_first() {
+        let (mut timeline, _, [a, _]) = timeline();
+        // The outgoing clip's end comes before the incoming clip's start.
+        let snapped = timeline.snap(&frames(96), &options(0)).unwrap();
+        assert_eq!((snapped.source, snapped.item_id), (SnapSource::ClipEnd, Some(a)));
+
+        // Equal priority and distance on both sides: the earlier marker,
+        // which comes first in the timeline's time-ordered list.
+        timeline.add_marker(Marker::new("late", frames(14)));
+        timeline.add_marker(Marker::new("early", frames(10)));
+        assert_eq!(timeline.snap(&frames(12), &options(2)).unwrap().time, frames(10));
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let (timeline, _, _) = timeline();
+        assert_eq!(timeline.snap(&frames(202), &options(2)).unwrap().time, frames(200));
+        assert_eq!(timeline.snap(&frames(198), &options(2)).unwrap().time, frames(200));
+        assert!(timeline.snap(&frames(203), &options(2)).is_none());
+        assert!(timeline.snap(&frames(201), &options(0)).is_none());
+
+        // 20 pixels at 480 pixels a second is one frame.
+        let pixels = SnapOptions::new(SnapTolerance::Pixels { pixels: 20.0, pixels_per_second: 480.0 });
+        assert_eq!(timeline.snap(&frames(201), &pixels).unwrap().time, frames(200));
+        assert!(timeline.snap(&frames(202), &pixels).is_none());
+        let unzoomed = SnapOptions::new(SnapTolerance::Pixels { pixels: 20.0, pixels_per_second: 0.0 });
+        assert!(timeline.snap(&frames(201), &unzoomed).is_none());
+    }
+
+    #[test]
+    fn contexts_snap_like_the_timeline() {
+        let (timeline, _, _) = timeline();
+        let options = options(3);
+        let context = timeline.snap_context(&options);
+        assert_eq!(context.targets().count(), timeline.snap_targets(&options).len());
+        for frame in 0..250 {
+            assert_eq!(context.snap(&frames(frame)).cloned(), timeline.snap(&frames(frame), &options));
+        }
+    }
+}
@@ -1,31 +1,159 @@
 use serde::{Deserialize, Serialize};
-use crate::RationalTime;
+use crate::{RationalTime, TimelineError};
 
+/// Loading fails for ranges whose end overflows, so `end` and the queries
+/// built on it do not panic on loaded ranges.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "TimeRangeRepr")]
 pub struct TimeRange {
     pub start: RationalTime,
     pub duration: RationalTime,
 }
 
+#[derive(Deserialize)]
+struct TimeRangeRepr {
+    start: RationalTime,
+    duration: RationalTime,
+}
+
+impl TryFrom<TimeRangeRepr> for TimeRange {
+    type Error = TimelineError;
+
+    fn try_from(repr: TimeRangeRepr) -> Result<Self, Self::Error> {
+        let range = Self::new(repr.start, repr.duration);
+        range.checked_end()?;
+        Ok(range)
+    }
+}
+
+/// Methods that add or subtract times panic where [`RationalTime`]'s
+/// operators do; each has a `checked_` form that returns the error
+/// instead, for input that did not come from this crate.
 impl TimeRange {
     pub fn new(start: RationalTime, duration: RationalTime) -> Self {
         Self { start, duration }
     }
 
+    pub fn from_start_end(start: RationalTime, end: RationalTime) -> Self {
+        Self { start, duration: end - start }
+    }
+
+    pub fn checked_from_start_end(start: RationalTime, end: RationalTime) -> Result<Self, TimelineError> {
+        Ok(Self { start, duration: end.checked_sub(&start)? })
+    }
+
     pub fn end(&self) -> RationalTime {
-        self.start.add(&self.duration)
+        self.start + self.duration
+    }
+
+    pub fn checked_end(&self) -> Result<RationalTime, TimelineError> {
+        self.start.checked_add(&self.duration)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.duration.value <= 0
     }
 
     pub fn contains(&self, time: &RationalTime) -> bool {
-        let end = self.end();
-        time.to_seconds() >= self.start.to_seconds() && time.to_seconds() < end.to_seconds()
+        *time >= self.start && *time < self.end()
+    }
+
+    pub fn contains_range(&self, other: &TimeRange) -> bool {
+        other.start >= self.start && other.end() <= self.end()
     }
 
     pub fn overlaps(&self, other: &TimeRange) -> bool {
-        let self_end = self.end();
-        let other_end = other.end();
-        
-        self.start.to_seconds() < other_end.to_seconds() && other.start.to_seconds() < self_end.to_seconds()
+        self.start < other.end() && other.start < self.end()
+    }
+
+    pub fn intersection(&self, other: &TimeRange) -> Option<TimeRange> {
+        self.checked_intersection(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn checked_intersection(&self, other: &TimeRange) -> Result<Option<TimeRange>, TimelineError> {
+        let start = self.start.max(other.start);
+        let end = self.checked_end()?.min(other.checked_end()?);
+        if start < end {
+            Ok(Some(Self::checked_from_start_end(start, end)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The smallest range covering both ranges, including any gap between them.
+    pub fn union_hull(&self, other: &TimeRange) -> TimeRange {
+        self.checked_union_hull(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn checked_union_hull(&self, other: &TimeRange) -> Result<TimeRange, TimelineError> {
+        let start = self.start.min(other.start);
+        let end = self.checked_end()?.max(other.checked_end()?);
+        Self::checked_from_start_end(start, end)
+    }
+
+    /// Clamps both ends into `bounds`. A range lying entirely outside
+    /// collapses to an empty range at the nearest edge of `bounds`.
+    pub fn clamped_to(&self, bounds: &TimeRange) -> TimeRange {
+        self.checked_clamped_to(bounds).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn checked_clamped_to(&self, bounds: &TimeRange) -> Result<TimeRange, TimelineError> {
+        let bounds_end = bounds.checked_end()?;
+        let clamp = |time: RationalTime| time.max(bounds.start).min(bounds_end);
+        Self::checked_from_start_end(clamp(self.start), clamp(self.checked_end()?))
+    }
+
+    /// Splits into `[start, time)` and `[time, end)`, or `None` unless
+    /// `time` falls strictly inside the range.
+    pub fn split_at(&self, time: &RationalTime) -> Option<(TimeRange, TimeRange)> {
+        self.checked_split_at(time).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn checked_split_at(&self, time: &RationalTime) -> Result<Option<(TimeRange, TimeRange)>, TimelineError> {
+        let end = self.checked_end()?;
+        if *time <= self.start || *time >= end {
+            return Ok(None);
+        }
+        Ok(Some((
+            Self::checked_from_start_end(self.start, *time)?,
+            Self::checked_from_start_end(*time, end)?,
+        )))
+    }
+
+    /// The parts of this range not covered by `other`, in time order.
+    pub fn subtract(&self, other: &TimeRange) -> Vec<TimeRange> {
+        self.checked_subtract(other).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn checked_subtract(&self, other: &TimeRange) -> Result<Vec<TimeRange>, TimelineError> {
+        let (end, other_end) = (self.checked_end()?, other.checked_end()?);
+        if !(self.start < other_end && other.start < end) {
+            return Ok(if self.is_empty() { Vec::new() } else { vec![*self] });
+        }
+        let mut remaining = Vec::with_capacity(2);
+        if self.start < other.start {
+            remaining.push(Self::checked_from_start_end(self.start, other.start)?);
+        }
+        if other_end < end {
+            remaining.push(Self::checked_from_start_end(other_end, end)?);
+        }
+        Ok(remaining)
+    }
+
+    pub fn extended_by(&self, amount: RationalTime) -> TimeRange {
+        Self::new(self.start, self.duration + amount)
+    }
+
+    pub fn checked_extended_by(&self, amount: RationalTime) -> Result<TimeRange, TimelineError> {
+        Ok(Self::new(self.start, self.duration.checked_add(&amount)?))
+    }
+
+    pub fn shifted_by(&self, offset: RationalTime) -> TimeRange {
+        Self::new(self.start + offset, self.duration)
+    }
+
+    pub fn checked_shifted_by(&self, offset: RationalTime) -> Result<TimeRange, TimelineError> {
+        Ok(Self::new(self.start.checked_add(&offset)?, self.duration))
     }
 }
 
@@ -37,3 +165,125 @@ impl Default for TimeRange {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FrameRate;
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// Two prime rates whose common rate does not fit a `u32`.
+    fn incompatible() -> (TimeRange, TimeRange) {
+        let a = FrameRate::new(4_294_967_291, 1);
+        let b = FrameRate::new(4_294_967_279, 1);
+        (
+            TimeRange::new(RationalTime::new(1, a), RationalTime::new(10, a)),
+            TimeRange::new(RationalTime::new(2, b), RationalTime::new(10, b)),
+        )
+    }
+
+    #[test]
+    fn emptiness() {
+        assert!(range(10, 10).is_empty());
+        assert!(TimeRange::new(frames(10), frames(-1)).is_empty());
+        assert!(!range(10, 11).is_empty());
+        assert!(TimeRange::default().is_empty());
+    }
+
+    #[test]
+    fn intersection_and_hull() {
+        assert_eq!(range(0, 48).intersection(&range(24, 96)), Some(range(24, 48)));
+        assert_eq!(range(0, 96).intersection(&range(24, 48)), Some(range(24, 48)));
+        // Touching ranges share no frame, and empty ranges meet nothing.
+        assert_eq!(range(0, 48).intersection(&range(48, 96)), None);
+        assert_eq!(range(0, 48).intersection(&range(24, 24)), None);
+
+        assert_eq!(range(0, 24).union_hull(&range(48, 96)), range(0, 96));
+        assert_eq!(range(48, 96).union_hull(&range(0, 24)), range(0, 96));
+        assert_eq!(range(0, 96).union_hull(&range(24, 24)), range(0, 96));
+    }
+
+    #[test]
+    fn clamping() {
+        let bounds = range(24, 96);
+        assert_eq!(range(48, 72).clamped_to(&bounds), range(48, 72));
+        assert_eq!(range(0, 48).clamped_to(&bounds), range(24, 48));
+        assert_eq!(range(72, 120).clamped_to(&bounds), range(72, 96));
+        assert_eq!(range(0, 12).clamped_to(&bounds), range(24, 24));
+        assert_eq!(range(100, 120).clamped_to(&bounds), range(96, 96));
+    }
+
+    #[test]
+    fn splitting() {
+        assert_eq!(range(0, 96).split_at(&frames(24)), Some((range(0, 24), range(24, 96))));
+        assert_eq!(range(0, 96).split_at(&frames(0)), None);
+        assert_eq!(range(0, 96).split_at(&frames(96)), None);
+        assert_eq!(range(0, 96).split_at(&frames(100)), None);
+        assert_eq!(range(10, 10).split_at(&frames(10)), None);
+    }
+
+    #[test]
+    fn subtracting() {
+        assert_eq!(range(0, 96).subtract(&range(24, 48)), vec![range(0, 24), range(48, 96)]);
+        assert_eq!(range(0, 96).subtract(&range(0, 48)), vec![range(48, 96)]);
+        assert_eq!(range(0, 96).subtract(&range(48, 120)), vec![range(0, 48)]);
+        assert_eq!(range(24, 48).subtract(&range(0, 96)), vec![]);
+        assert_eq!(range(0, 48).subtract(&range(48, 96)), vec![range(0, 48)]);
+        assert_eq!(range(10, 10).subtract(&range(48, 96)), vec![]);
+    }
+
+    #[test]
+    fn extending_and_shifting() {
+        assert_eq!(range(0, 48).extended_by(frames(24)), range(0, 72));
+        assert_eq!(range(0, 48).extended_by(frames(-48)), range(0, 0));
+        assert_eq!(range(0, 48).shifted_by(frames(-12)), range(-12, 36));
+    }
+
+    #[test]
+    fn mixed_rates() {
+        let ntsc = |value| RationalTime::new(value, FrameRate::FPS_29_97);
+        // 1001 frames at 29.97 is 33.4001s, or 801.6 frames at 24.
+        let a = TimeRange::new(frames(0), frames(800));
+        let b = TimeRange::new(ntsc(0), ntsc(1001));
+        assert_eq!(a.union_hull(&b).end(), ntsc(1001));
+        assert_eq!(a.intersection(&b), Some(a));
+        assert_eq!(b.subtract(&a), vec![TimeRange::from_start_end(frames(800), ntsc(1001))]);
+        assert_eq!(a.split_at(&ntsc(500)).unwrap().1.start, ntsc(500));
+    }
+
+    #[test]
+    fn checked_forms_report_incompatible_rates() {
+        let (a, b) = incompatible();
+        assert!(matches!(a.checked_union_hull(&b), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_intersection(&b), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_subtract(&b), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_clamped_to(&b), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_shifted_by(b.start), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_extended_by(b.start), Err(TimelineError::IncompatibleRates(..))));
+        assert!(matches!(a.checked_split_at(&b.start), Err(TimelineError::IncompatibleRates(..))));
+        assert_eq!(a.checked_union_hull(&a).unwrap(), a);
+    }
+
+    #[test]
+    fn ranges_whose_end_overflows_do_not_load() {
+        let loaded: TimeRange = serde_json::from_value(serde_json::json!({
+            "start": { "value": 10, "rate": 24 },
+            "duration": { "value": 20, "rate": 24 },
+        }))
+        .unwrap();
+        assert_eq!(loaded, TimeRange::new(frames(10), frames(20)));
+
+        let overflowing = serde_json::json!({
+            "start": { "value": i64::MAX, "rate": 24 },
+            "duration": { "value": 1, "rate": 24 },
+        });
+        assert!(serde_json::from_value::<TimeRange>(overflowing).is_err());
+    }
+}
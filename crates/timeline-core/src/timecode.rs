@@ -0,0 +1,259 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::{FrameRate, RationalTime, RoundingMode, TimelineError};
+
+/// How frame counts beyond a single 24 hour day are labelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimecodeWrap {
+    /// Roll over at midnight, so negative offsets count back from 24:00:00:00.
+    Wrap,
+    /// Let the hours field grow past 23 and keep the sign of negative offsets.
+    Extend,
+    /// Fail for anything outside `00:00:00:00..24:00:00:00`.
+    Reject,
+}
+
+/// An SMPTE timecode label. Frame labels count at the nominal rate
+/// (30 for 29.97), with drop-frame skipping labels to stay on wall clock time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    pub negative: bool,
+    pub drop_frame: bool,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    pub fn from_frames(
+        frames: i64,
+        rate: FrameRate,
+        drop_frame: bool,
+        wrap: TimecodeWrap,
+    ) -> Result<Self, TimelineError> {
+        rate.validate()?;
+        if drop_frame && !supports_drop_frame(rate) {
+            return Err(TimelineError::InvalidTimecode(format!(
+                "drop-frame timecode is not defined at {} fps",
+                rate
+            )));
+        }
+
+        let day = frames_per_day(rate, drop_frame);
+        let frames = match wrap {
+            TimecodeWrap::Wrap => frames.rem_euclid(day),
+            TimecodeWrap::Extend => frames,
+            TimecodeWrap::Reject if frames < 0 || frames >= day => {
+                return Err(TimelineError::InvalidTimecode(format!(
+                    "frame {} is outside a 24 hour day",
+                    frames
+                )));
+            }
+            TimecodeWrap::Reject => frames,
+        };
+
+        let nominal = rate.nominal() as i64;
+        let mut label = frames.abs();
+        if drop_frame {
+            label += dropped_labels_before(label, nominal);
+        }
+
+        Ok(Self {
+            hours: (label / (nominal * 3600)) as u32,
+            minutes: (label / (nominal * 60) % 60) as u32,
+            seconds: (label / nominal % 60) as u32,
+            frames: (label % nominal) as u32,
+            negative: frames < 0,
+            drop_frame,
+            rate,
+        })
+    }
+
+    pub fn from_time(
+        time: &RationalTime,
+        rate: FrameRate,
+        drop_frame: bool,
+        wrap: TimecodeWrap,
+    ) -> Result<Self, TimelineError> {
+        let frames = time.rescaled_with(rate, RoundingMode::Floor).value;
+        Self::from_frames(frames, rate, drop_frame, wrap)
+    }
+
+    /// Parses `HH:MM:SS:FF`, or `HH:MM:SS;FF` for drop-frame, with an
+    /// optional leading `-`. Labels outside a single day are handled by
+    /// `wrap` as in [`Timecode::from_frames`].
+    pub fn parse(text: &str, rate: FrameRate, wrap: TimecodeWrap) -> Result<Self, TimelineError> {
+        rate.validate()?;
+        let invalid = || TimelineError::InvalidTimecode(text.to_string());
+
+        let trimmed = text.trim();
+        let (negative, body) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let drop_frame = body.contains(';');
+        let fields = body
+            .split([':', ';'])
+            .map(|field| field.parse::<u32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [hours, minutes, seconds, frames] = fields[..] else {
+            return Err(invalid());
+        };
+
+        if drop_frame && !supports_drop_frame(rate) {
+            return Err(TimelineError::InvalidTimecode(format!(
+                "drop-frame timecode is not defined at {} fps",
+                rate
+            )));
+        }
+        if minutes >= 60 || seconds >= 60 || frames >= rate.nominal() {
+            return Err(invalid());
+        }
+        if drop_frame && seconds == 0 && minutes % 10 != 0 && frames < drop_count(rate) {
+            return Err(TimelineError::InvalidTimecode(format!(
+                "{} is a dropped frame label",
+                text
+            )));
+        }
+
+        let timecode = Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+            negative,
+            drop_frame,
+            rate,
+        };
+        Self::from_frames(timecode.to_frames(), rate, drop_frame, wrap)
+    }
+
+    pub fn to_frames(&self) -> i64 {
+        let nominal = self.rate.nominal() as i64;
+        let total_minutes = self.hours as i64 * 60 + self.minutes as i64;
+        let mut frames = (total_minutes * 60 + self.seconds as i64) * nominal + self.frames as i64;
+        if self.drop_frame {
+            frames -= drop_count(self.rate) as i64 * (total_minutes - total_minutes / 10);
+        }
+        if self.negative { -frames } else { frames }
+    }
+
+    pub fn to_time(&self) -> RationalTime {
+        RationalTime::new(self.to_frames(), self.rate)
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        let width = self.rate.nominal().saturating_sub(1).to_string().len().max(2);
+        write!(
+            f,
+            "{}{:02}:{:02}:{:02}{}{:0width$}",
+            if self.negative { "-" } else { "" },
+            self.hours,
+            self.minutes,
+            self.seconds,
+            separator,
+            self.frames,
+            width = width
+        )
+    }
+}
+
+pub fn supports_drop_frame(rate: FrameRate) -> bool {
+    rate.is_ntsc() && rate.nominal().is_multiple_of(30)
+}
+
+fn drop_count(rate: FrameRate) -> u32 {
+    rate.nominal() / 15
+}
+
+fn frames_per_day(rate: FrameRate, drop_frame: bool) -> i64 {
+    let nominal = rate.nominal() as i64;
+    if drop_frame {
+        let drop = drop_count(rate) as i64;
+        (nominal * 600 - drop * 9) * 6 * 24
+    } else {
+        nominal * 86_400
+    }
+}
+
+fn dropped_labels_before(frames: i64, nominal: i64) -> i64 {
+    let drop = nominal / 15;
+    let per_minute = nominal * 60 - drop;
+    let per_ten_minutes = nominal * 600 - drop * 9;
+    let blocks = frames / per_ten_minutes;
+    let remainder = frames % per_ten_minutes;
+    let mut skipped = drop * 9 * blocks;
+    if remainder > drop {
+        skipped += drop * ((remainder - drop) / per_minute);
+    }
+    skipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label(frames: i64, rate: FrameRate) -> String {
+        Timecode::from_frames(frames, rate, true, TimecodeWrap::Reject).unwrap().to_string()
+    }
+
+    fn parse(text: &str, rate: FrameRate, wrap: TimecodeWrap) -> Result<Timecode, TimelineError> {
+        Timecode::parse(text, rate, wrap)
+    }
+
+    #[test]
+    fn drop_frame_labels_skip_at_each_minute_but_the_tenth() {
+        let rate = FrameRate::FPS_29_97;
+        assert_eq!(label(1799, rate), "00:00:59;29");
+        assert_eq!(label(1800, rate), "00:01:00;02");
+        assert_eq!(label(17981, rate), "00:09:59;29");
+        assert_eq!(label(17982, rate), "00:10:00;00");
+        assert_eq!(label(17982 * 6 * 24 - 1, rate), "23:59:59;29");
+
+        let rate = FrameRate::FPS_59_94;
+        assert_eq!(label(3599, rate), "00:00:59;59");
+        assert_eq!(label(3600, rate), "00:01:00;04");
+        assert_eq!(label(35964, rate), "00:10:00;00");
+    }
+
+    #[test]
+    fn drop_frame_labels_round_trip() {
+        for rate in [FrameRate::FPS_29_97, FrameRate::FPS_59_94] {
+            let day = frames_per_day(rate, true);
+            for frames in (0..day).step_by(997).chain(day - 100..day) {
+                let text = label(frames, rate);
+                let parsed = parse(&text, rate, TimecodeWrap::Reject).unwrap();
+                assert_eq!(parsed.to_frames(), frames, "{text}");
+                assert_eq!(parsed.to_string(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn dropped_labels_do_not_parse() {
+        let rate = FrameRate::FPS_29_97;
+        for text in ["00:01:00;00", "00:01:00;01", "01:59:00;01"] {
+            assert!(matches!(parse(text, rate, TimecodeWrap::Extend), Err(TimelineError::InvalidTimecode(_))), "{text}");
+        }
+        assert!(parse("00:10:00;00", rate, TimecodeWrap::Extend).is_ok());
+        assert!(parse("00:01:00;03", FrameRate::FPS_59_94, TimecodeWrap::Extend).is_err());
+        assert!(parse("00:01:00;00", FrameRate::FPS_25, TimecodeWrap::Extend).is_err());
+    }
+
+    #[test]
+    fn parsing_applies_the_day_policy() {
+        let rate = FrameRate::FPS_25;
+        assert_eq!(parse("25:00:00:10", rate, TimecodeWrap::Wrap).unwrap().to_string(), "01:00:00:10");
+        assert_eq!(parse("-00:00:01:00", rate, TimecodeWrap::Wrap).unwrap().to_string(), "23:59:59:00");
+        assert_eq!(parse("25:00:00:10", rate, TimecodeWrap::Extend).unwrap().hours, 25);
+        assert_eq!(parse("-00:00:01:00", rate, TimecodeWrap::Extend).unwrap().to_frames(), -25);
+        assert!(parse("24:00:00:00", rate, TimecodeWrap::Reject).is_err());
+        assert!(parse("-00:00:00:01", rate, TimecodeWrap::Reject).is_err());
+        assert_eq!(parse("23:59:59:24", rate, TimecodeWrap::Reject).unwrap().to_frames(), 86_400 * 25 - 1);
+    }
+}
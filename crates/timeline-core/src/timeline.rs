@@ -1,10 +1,12 @@
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::{RationalTime, Track, TrackKind, Clip};
+use crate::marker::deserialize_markers;
+use crate::{Clip, EditResult, FrameRate, Marker, MulticamSource, RationalTime, TimelineError, Track, TrackKind};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TimelineMetadata {
-    pub frame_rate: u32,
+    pub frame_rate: FrameRate,
     pub width: u32,
     pub height: u32,
     pub sample_rate: u32,
@@ -13,7 +15,7 @@ pub struct TimelineMetadata {
 impl Default for TimelineMetadata {
     fn default() -> Self {
         Self {
-            frame_rate: 24,
+            frame_rate: FrameRate::FPS_24,
             width: 1920,
             height: 1080,
             sample_rate: 48000,
@@ -27,17 +29,29 @@ pub struct Timeline {
     pub name: String,
     pub metadata: TimelineMetadata,
     pub tracks: Vec<Track>,
+    /// Ordered by time.
+    #[serde(default, deserialize_with = "deserialize_markers")]
+    pub(crate) markers: Vec<Marker>,
+    /// The multicam sources the timeline's clips play, by id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) multicams: BTreeMap<Uuid, MulticamSource>,
     pub global_start_time: RationalTime,
 }
 
 impl Timeline {
-    pub fn new(name: impl Into<String>) -> Self {
+    pub fn new(name: impl Into<String>, frame_rate: impl Into<FrameRate>) -> Self {
+        let frame_rate = frame_rate.into();
         Self {
             id: Uuid::new_v4(),
             name: name.into(),
-            metadata: TimelineMetadata::default(),
+            metadata: TimelineMetadata {
+                frame_rate,
+                ..TimelineMetadata::default()
+            },
             tracks: Vec::new(),
-            global_start_time: RationalTime::default(),
+            markers: Vec::new(),
+            multicams: BTreeMap::new(),
+            global_start_time: RationalTime::new(0, frame_rate),
         }
     }
 
@@ -46,7 +60,8 @@ impl Timeline {
         self
     }
 
-    pub fn with_frame_rate(mut self, frame_rate: u32) -> Self {
+    pub fn with_frame_rate(mut self, frame_rate: impl Into<FrameRate>) -> Self {
+        let frame_rate = frame_rate.into();
         self.metadata.frame_rate = frame_rate;
         self.global_start_time = RationalTime::new(0, frame_rate);
         self
@@ -64,19 +79,67 @@ impl Timeline {
         }
     }
 
+    pub fn track(&self, track_id: Uuid) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.id == track_id)
+    }
+
+    pub fn track_mut(&mut self, track_id: Uuid) -> Option<&mut Track> {
+        self.tracks.iter_mut().find(|t| t.id == track_id)
+    }
+
+    pub fn add_clip_to_track(&mut self, track_id: Uuid, clip: Clip) -> Result<(), TimelineError> {
+        self.track_mut(track_id)
+            .ok_or(TimelineError::TrackNotFound(track_id))?
+            .add_clip(clip)
+    }
+
+    /// Removes a clip and every clip linked to it, returning the one asked
+    /// for.
+    pub fn remove_clip(&mut self, track_id: Uuid, clip_id: Uuid) -> Result<Clip, TimelineError> {
+        let mut removed = None;
+        self.edit_linked(track_id, clip_id, |track, id| {
+            let clip = track.remove_clip(id).ok_or(TimelineError::ClipNotFound(id))?;
+            if id == clip_id {
+                removed = Some(clip);
+            }
+            Ok(EditResult::default())
+        })?;
+        removed.ok_or(TimelineError::ClipNotFound(clip_id))
+    }
+
+    /// Splits a clip at `at`, returning the id of the new right-hand clip.
+    /// Linked clips that span `at` are cut too.
+    pub fn split_clip(&mut self, track_id: Uuid, clip_id: Uuid, at: RationalTime) -> Result<Uuid, TimelineError> {
+        let mut tail_id = clip_id;
+        self.edit_linked(track_id, clip_id, |track, id| {
+            let spans = track.clip(id).is_some_and(|c| c.timeline_range.split_at(&at).is_some());
+            if id == clip_id {
+                tail_id = track.split_clip(id, at)?;
+            } else if spans {
+                track.split_clip(id, at)?;
+            }
+            Ok(EditResult::default())
+        })?;
+        Ok(tail_id)
+    }
+
+    /// Where the last picture, sound or caption ends. Data tracks ride
+    /// along without extending the programme.
     pub fn duration(&self) -> RationalTime {
         self.tracks
             .iter()
-            .filter(|t| t.enabled)
+            .filter(|t| t.enabled && t.kind != TrackKind::Data)
             .map(|t| t.duration())
-            .max_by(|a, b| a.to_seconds().partial_cmp(&b.to_seconds()).unwrap())
+            .max()
             .unwrap_or_else(|| RationalTime::new(0, self.metadata.frame_rate))
     }
 
+    /// The first rendered clip at `time`; subtitle and data tracks are
+    /// skipped.
     pub fn clip_at_time(&self, time: &RationalTime) -> Option<&Clip> {
         self.tracks
             .iter()
-            .filter(|t| t.enabled)
+            .filter(|t| t.enabled && t.kind.is_rendered())
             .find_map(|track| track.clip_at_time(time))
     }
 
@@ -91,6 +154,6 @@ impl Timeline {
 
 impl Default for Timeline {
     fn default() -> Self {
-        Self::new("Timeline")
+        Self::new("Timeline", FrameRate::default())
     }
 }
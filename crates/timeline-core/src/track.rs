@@ -1,11 +1,115 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
-use crate::{Clip, RationalTime, TimeRange};
+use crate::caption::deserialize_captions;
+use crate::marker::deserialize_markers;
+use crate::{Caption, Clip, Marker, RationalTime, TimeRange, TimelineError, Transition};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TrackKind {
     Video,
     Audio,
+    /// Holds captions instead of clips.
+    Subtitle,
+    /// Clips of non-picture media such as telemetry, kept in sync but
+    /// never rendered.
+    Data,
+}
+
+impl TrackKind {
+    pub fn holds_clips(&self) -> bool {
+        *self != TrackKind::Subtitle
+    }
+
+    /// Whether the track's clips are part of the rendered picture or sound.
+    pub fn is_rendered(&self) -> bool {
+        matches!(self, TrackKind::Video | TrackKind::Audio)
+    }
+}
+
+/// Empty space in a track's sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Gap {
+    pub duration: RationalTime,
+}
+
+impl Gap {
+    pub fn new(duration: RationalTime) -> Self {
+        Self { duration }
+    }
+}
+
+/// Tracks played in parallel as a single item of an outer track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stack {
+    pub id: Uuid,
+    pub name: String,
+    pub tracks: Vec<Track>,
+}
+
+impl Stack {
+    pub fn new(name: impl Into<String>, tracks: Vec<Track>) -> Self {
+        Self { id: Uuid::new_v4(), name: name.into(), tracks }
+    }
+
+    pub fn duration(&self) -> RationalTime {
+        self.tracks
+            .iter()
+            .map(Track::duration)
+            .max()
+            .unwrap_or_else(|| RationalTime::new(0, 24))
+    }
+}
+
+/// One entry of a track laid out as a sequence, the way OTIO models it:
+/// each item starts where the previous one ended. Transitions sit between
+/// items and take up no time of their own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TrackItem {
+    Clip(Box<Clip>),
+    Gap(Gap),
+    Transition(Transition),
+    Stack(Stack),
+}
+
+impl TrackItem {
+    pub fn duration(&self) -> RationalTime {
+        match self {
+            TrackItem::Clip(clip) => clip.timeline_range.duration,
+            TrackItem::Gap(gap) => gap.duration,
+            TrackItem::Transition(transition) => RationalTime::new(0, transition.duration.rate),
+            TrackItem::Stack(stack) => stack.duration(),
+        }
+    }
+}
+
+/// Merges neighbouring gaps and drops empty ones.
+pub fn normalize_gaps(items: Vec<TrackItem>) -> Vec<TrackItem> {
+    let mut normalized: Vec<TrackItem> = Vec::with_capacity(items.len());
+    for item in items {
+        match (normalized.last_mut(), item) {
+            (_, TrackItem::Gap(gap)) if gap.duration.value <= 0 => {}
+            (Some(TrackItem::Gap(previous)), TrackItem::Gap(gap)) => {
+                previous.duration = previous.duration + gap.duration;
+            }
+            (_, item) => normalized.push(item),
+        }
+    }
+    normalized
+}
+
+/// The timeline range of every item, derived purely from sequence order
+/// starting at `start`.
+pub fn item_ranges(items: &[TrackItem], start: RationalTime) -> Vec<TimeRange> {
+    let mut cursor = start;
+    items
+        .iter()
+        .map(|item| {
+            let range = TimeRange::new(cursor, item.duration());
+            cursor = range.end();
+            range
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -13,9 +117,34 @@ pub struct Track {
     pub id: Uuid,
     pub name: String,
     pub kind: TrackKind,
-    pub clips: Vec<Clip>,
+    /// Ordered by timeline start and never overlapping.
+    #[serde(deserialize_with = "deserialize_clips")]
+    pub(crate) clips: Vec<Clip>,
+    #[serde(default)]
+    pub(crate) transitions: Vec<Transition>,
+    #[serde(default, deserialize_with = "deserialize_markers")]
+    pub(crate) markers: Vec<Marker>,
+    /// Only on subtitle tracks. Ordered by start and never overlapping.
+    #[serde(default, deserialize_with = "deserialize_captions")]
+    pub(crate) captions: Vec<Caption>,
     pub enabled: bool,
     pub locked: bool,
+    #[serde(default)]
+    pub solo: bool,
+    #[serde(default)]
+    pub muted: bool,
+    /// Linear gain applied to everything on the track, as the TS
+    /// `Track.volume` slider stores it. [`Track::volume_db`] and
+    /// [`Track::set_volume_db`] work in dB like clip gain.
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+    /// Stereo position from -1 (left) to 1 (right).
+    #[serde(default)]
+    pub pan: f64,
+}
+
+fn default_volume() -> f64 {
+    1.0
 }
 
 impl Track {
@@ -25,39 +154,260 @@ impl Track {
             name: name.into(),
             kind,
             clips: Vec::new(),
+            transitions: Vec::new(),
+            markers: Vec::new(),
+            captions: Vec::new(),
             enabled: true,
             locked: false,
+            solo: false,
+            muted: false,
+            volume: 1.0,
+            pan: 0.0,
         }
     }
 
-    pub fn add_clip(&mut self, clip: Clip) {
-        self.clips.push(clip);
+    pub fn clips(&self) -> &[Clip] {
+        &self.clips
+    }
+
+    pub fn clip(&self, clip_id: Uuid) -> Option<&Clip> {
+        self.clips.iter().find(|c| c.id == clip_id)
+    }
+
+    /// Inserts a clip in timeline order, failing if it would overlap
+    /// another clip.
+    pub fn add_clip(&mut self, clip: Clip) -> Result<(), TimelineError> {
+        if !self.kind.holds_clips() {
+            return Err(no_clips_error(self));
+        }
+        let index = self.clips.partition_point(|c| c.timeline_range.start < clip.timeline_range.start);
+        let neighbours = [index.checked_sub(1), Some(index)];
+        for existing in neighbours.into_iter().flatten().filter_map(|i| self.clips.get(i)) {
+            if existing.timeline_range.overlaps(&clip.timeline_range) {
+                let time = existing.timeline_range.start.max(clip.timeline_range.start);
+                return Err(TimelineError::ClipOverlap { time: time.to_seconds() });
+            }
+        }
+        self.clips.insert(index, clip);
+        Ok(())
     }
 
+    /// Removes a clip along with any transition on either of its edges.
     pub fn remove_clip(&mut self, clip_id: uuid::Uuid) -> Option<Clip> {
-        if let Some(pos) = self.clips.iter().position(|c| c.id == clip_id) {
-            Some(self.clips.remove(pos))
-        } else {
-            None
+        let pos = self.clips.iter().position(|c| c.id == clip_id)?;
+        self.transitions
+            .retain(|t| t.from_clip_id != Some(clip_id) && t.to_clip_id != Some(clip_id));
+        Some(self.clips.remove(pos))
+    }
+
+    /// Changes a clip in place, re-checking its position afterwards. The
+    /// clip is left untouched if the update would make it overlap another.
+    pub fn update_clip<F>(&mut self, clip_id: Uuid, update: F) -> Result<(), TimelineError>
+    where
+        F: FnOnce(&mut Clip),
+    {
+        let previous = self.clips.clone();
+        let transitions = self.transitions.clone();
+        let pos = self.clips.iter().position(|c| c.id == clip_id).ok_or(TimelineError::ClipNotFound(clip_id))?;
+        let mut clip = self.clips.remove(pos);
+        update(&mut clip);
+        let result = self.add_clip(clip).and_then(|_| self.reconcile_transitions(&previous));
+        if result.is_err() {
+            self.clips = previous;
+            self.transitions = transitions;
         }
+        result
     }
 
     pub fn clip_at_time(&self, time: &RationalTime) -> Option<&Clip> {
-        self.clips.iter().find(|clip| clip.timeline_range.contains(time) && clip.enabled)
+        let index = self.clips.partition_point(|c| c.timeline_range.start <= *time);
+        let clip = self.clips.get(index.checked_sub(1)?)?;
+        (clip.timeline_range.contains(time) && clip.enabled).then_some(clip)
+    }
+
+    /// Every clip that overlaps `range`, in timeline order.
+    pub fn clips_in_range(&self, range: &TimeRange) -> &[Clip] {
+        let first = self.clips.partition_point(|c| c.timeline_range.end() <= range.start);
+        let last = self.clips.partition_point(|c| c.timeline_range.start < range.end());
+        &self.clips[first..last.max(first)]
+    }
+
+    /// The last clip that ends at or before `time`.
+    pub fn prev_clip(&self, time: &RationalTime) -> Option<&Clip> {
+        let index = self.clips.partition_point(|c| c.timeline_range.end() <= *time);
+        self.clips.get(index.checked_sub(1)?)
+    }
+
+    /// The first clip that starts at or after `time`.
+    pub fn next_clip(&self, time: &RationalTime) -> Option<&Clip> {
+        let index = self.clips.partition_point(|c| c.timeline_range.start < *time);
+        self.clips.get(index)
     }
 
+    /// Where the last enabled clip or caption ends.
     pub fn duration(&self) -> RationalTime {
-        self.clips
-            .iter()
-            .filter(|c| c.enabled)
-            .map(|c| c.timeline_range.end())
-            .max_by(|a, b| a.to_seconds().partial_cmp(&b.to_seconds()).unwrap())
-            .unwrap_or_else(|| RationalTime::new(0, 24))
+        let clips_end = self.clips.iter().rev().find(|c| c.enabled).map(|c| c.timeline_range.end());
+        let captions_end = self.captions.last().map(|c| c.range.end());
+        clips_end.max(captions_end).unwrap_or_else(|| RationalTime::new(0, 24))
+    }
+
+    /// The track as a sequence of clips separated by explicit gaps,
+    /// starting from time zero.
+    pub fn items(&self) -> Vec<TrackItem> {
+        let mut items = Vec::with_capacity(self.clips.len() * 2);
+        let mut cursor = match self.clips.first() {
+            Some(first) => RationalTime::new(0, first.timeline_range.start.rate),
+            None => return items,
+        };
+        for clip in &self.clips {
+            if clip.timeline_range.start > cursor {
+                items.push(TrackItem::Gap(Gap::new(clip.timeline_range.start - cursor)));
+            }
+            cursor = clip.timeline_range.end();
+            items.push(TrackItem::Clip(Box::new(clip.clone())));
+            if let Some(transition) = self.transitions.iter().find(|t| t.from_clip_id == Some(clip.id)) {
+                items.push(TrackItem::Transition(transition.clone()));
+            }
+        }
+        items
+    }
+
+    /// Builds a track from a sequence, placing each clip where the
+    /// preceding items end. A transition joins the clips on either side
+    /// of it, which must not be separated by a gap.
+    pub fn from_items(name: impl Into<String>, kind: TrackKind, items: Vec<TrackItem>) -> Result<Self, TimelineError> {
+        let mut track = Self::new(name, kind);
+        let items = normalize_gaps(items);
+        let rate = items.first().map(|item| item.duration().rate).unwrap_or_default();
+        let ranges = item_ranges(&items, RationalTime::new(0, rate));
+
+        let mut previous_clip: Option<Uuid> = None;
+        let mut pending: Option<(Uuid, Transition)> = None;
+        let mut joins = Vec::new();
+        for (item, range) in items.into_iter().zip(ranges) {
+            match item {
+                TrackItem::Clip(mut clip) => {
+                    clip.timeline_range = range;
+                    if let Some((from, transition)) = pending.take() {
+                        joins.push((from, clip.id, transition));
+                    }
+                    previous_clip = Some(clip.id);
+                    track.add_clip(*clip)?;
+                }
+                TrackItem::Gap(_) => {
+                    previous_clip = None;
+                    if pending.is_some() {
+                        return Err(TimelineError::InvalidState("a transition cannot lead into a gap".to_string()));
+                    }
+                }
+                TrackItem::Transition(transition) => {
+                    let from = previous_clip.ok_or_else(|| {
+                        TimelineError::InvalidState("a transition must follow a clip".to_string())
+                    })?;
+                    pending = Some((from, transition));
+                }
+                TrackItem::Stack(stack) => {
+                    return Err(TimelineError::OperationFailed(format!(
+                        "nested stack '{}' cannot be stored on a flat track",
+                        stack.name
+                    )));
+                }
+            }
+        }
+        if pending.is_some() {
+            return Err(TimelineError::InvalidState("a transition must be followed by a clip".to_string()));
+        }
+        for (from, to, transition) in joins {
+            track.add_transition(from, to, transition)?;
+        }
+        Ok(track)
+    }
+
+    /// Restores clip order after bulk changes and reports any overlap.
+    pub(crate) fn normalize(&mut self) -> Result<(), TimelineError> {
+        self.clips.sort_by_key(|c| c.timeline_range.start);
+        check_overlaps(&self.clips)
     }
 }
 
+pub(crate) fn no_clips_error(track: &Track) -> TimelineError {
+    TimelineError::InvalidState(format!("{:?} track {} cannot hold clips", track.kind, track.id))
+}
+
+pub(crate) fn check_overlaps(sorted: &[Clip]) -> Result<(), TimelineError> {
+    for pair in sorted.windows(2) {
+        if pair[0].timeline_range.overlaps(&pair[1].timeline_range) {
+            return Err(TimelineError::ClipOverlap { time: pair[1].timeline_range.start.to_seconds() });
+        }
+    }
+    Ok(())
+}
+
+fn deserialize_clips<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Clip>, D::Error> {
+    let mut clips = Vec::<Clip>::deserialize(deserializer)?;
+    clips.sort_by_key(|c| c.timeline_range.start);
+    check_overlaps(&clips).map_err(serde::de::Error::custom)?;
+    Ok(clips)
+}
+
 impl Default for Track {
     fn default() -> Self {
         Self::new("Track", TrackKind::Video)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn clip(start: i64, duration: i64) -> Clip {
+        let source = TimeRange::new(frames(0), frames(duration));
+        Clip::new("shot", "shot.mov", source, TimeRange::new(frames(start), frames(duration)))
+    }
+
+    #[test]
+    fn items_round_trip_with_gaps() {
+        let mut track = Track::new("V1", TrackKind::Video);
+        track.add_clip(clip(24, 48)).unwrap();
+        track.add_clip(clip(96, 24)).unwrap();
+
+        let items = track.items();
+        let durations: Vec<_> = items.iter().map(TrackItem::duration).collect();
+        assert_eq!(durations, vec![frames(24), frames(48), frames(24), frames(24)]);
+        assert!(matches!(items[0], TrackItem::Gap(_)));
+
+        let rebuilt = Track::from_items("V1", TrackKind::Video, items).unwrap();
+        assert_eq!(rebuilt.clips(), track.clips());
+    }
+
+    #[test]
+    fn neighbouring_gaps_merge() {
+        let items = vec![
+            TrackItem::Gap(Gap::new(frames(10))),
+            TrackItem::Gap(Gap::new(frames(0))),
+            TrackItem::Gap(Gap::new(frames(14))),
+            TrackItem::Clip(Box::new(clip(0, 24))),
+        ];
+        let track = Track::from_items("V1", TrackKind::Video, items).unwrap();
+        assert_eq!(track.clips()[0].timeline_range.start, frames(24));
+        assert_eq!(normalize_gaps(track.items()).len(), 2);
+    }
+
+    #[test]
+    fn stacks_last_as_long_as_their_longest_track() {
+        let mut short = Track::new("V1", TrackKind::Video);
+        short.add_clip(clip(0, 24)).unwrap();
+        let mut long = Track::new("A1", TrackKind::Audio);
+        long.add_clip(clip(12, 48)).unwrap();
+        let stack = Stack::new("nest", vec![short, long]);
+        assert_eq!(TrackItem::Stack(stack.clone()).duration(), frames(60));
+        assert_eq!(Stack::new("empty", Vec::new()).duration(), frames(0));
+
+        let result = Track::from_items("V1", TrackKind::Video, vec![TrackItem::Stack(stack)]);
+        assert!(matches!(result, Err(TimelineError::OperationFailed(_))));
+    }
+}
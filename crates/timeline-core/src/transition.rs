@@ -1,38 +1,557 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::RationalTime;
+use crate::effect::{Color, EffectParameter, ParameterType, ParameterValue};
+use crate::{Clip, RationalTime, Timeline, TimelineError, Track, TrimEdge};
 
+/// Serialized with the TS schema's names. The aliases read projects saved
+/// before the names were aligned.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum TransitionType {
+    #[serde(alias = "Cut")]
     Cut,
+    #[serde(alias = "CrossDissolve")]
     CrossDissolve,
+    #[serde(rename = "dip", alias = "fade", alias = "DipToBlack")]
     DipToBlack,
+    #[serde(alias = "Wipe")]
     Wipe,
+    #[serde(alias = "Push")]
+    Push,
+    #[serde(alias = "Slide")]
+    Slide,
+    #[serde(alias = "Zoom")]
+    Zoom,
+    #[serde(alias = "Iris")]
+    Iris,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    /// The travel direction in degrees, clockwise from pointing right.
+    pub fn angle(&self) -> f64 {
+        match self {
+            Direction::Right => 0.0,
+            Direction::Down => 90.0,
+            Direction::Left => 180.0,
+            Direction::Up => 270.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps linear progress in `0..=1` onto the eased curve.
+    pub fn apply(&self, progress: f64) -> f64 {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Settings for the directional and shaped transitions. Kinds ignore the
+/// fields that do not apply to them, e.g. a cross dissolve only eases.
+/// Serialized as the `{ name, type, value }` list used by the TS schema.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "Vec<EffectParameter>", from = "Vec<EffectParameter>")]
+pub struct TransitionParameters {
+    pub direction: Direction,
+    /// Overrides `direction` for wipes at arbitrary angles, in degrees.
+    pub angle: Option<f64>,
+    /// Width of the feathered edge as a fraction of the frame, `0..=1`.
+    pub softness: f64,
+    /// Border drawn along the moving edge, in pixels.
+    pub border_width: f64,
+    pub border_color: Color,
+    pub easing: Easing,
+    /// Centre of zoom and iris shapes in normalized frame coordinates.
+    pub center_x: f64,
+    pub center_y: f64,
+}
+
+impl TransitionParameters {
+    pub fn effective_angle(&self) -> f64 {
+        self.angle.unwrap_or_else(|| self.direction.angle())
+    }
+}
+
+impl Default for TransitionParameters {
+    fn default() -> Self {
+        Self {
+            direction: Direction::Left,
+            angle: None,
+            softness: 0.0,
+            border_width: 0.0,
+            border_color: Color::black(),
+            easing: Easing::Linear,
+            center_x: 0.5,
+            center_y: 0.5,
+        }
+    }
+}
+
+impl From<TransitionParameters> for Vec<EffectParameter> {
+    fn from(params: TransitionParameters) -> Self {
+        let mut entries = vec![choice(
+            "direction",
+            params.direction,
+            &[Direction::Left, Direction::Right, Direction::Up, Direction::Down],
+        )];
+        if let Some(angle) = params.angle {
+            entries.push(EffectParameter::number("angle", angle));
+        }
+        entries.extend([
+            EffectParameter::number("softness", params.softness).with_range(0.0, 1.0),
+            EffectParameter::number("borderWidth", params.border_width),
+            EffectParameter::new("borderColor", ParameterType::Color, params.border_color),
+            choice(
+                "easing",
+                params.easing,
+                &[Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut],
+            ),
+            EffectParameter::number("centerX", params.center_x),
+            EffectParameter::number("centerY", params.center_y),
+        ]);
+        entries
+    }
+}
+
+impl From<Vec<EffectParameter>> for TransitionParameters {
+    fn from(entries: Vec<EffectParameter>) -> Self {
+        let mut params = Self::default();
+        for entry in entries {
+            let label = || entry.value.as_str().map(|text| serde_json::Value::String(text.to_string()));
+            match (entry.name.as_str(), &entry.value) {
+                ("direction", _) => {
+                    if let Some(direction) = label().and_then(|v| serde_json::from_value(v).ok()) {
+                        params.direction = direction;
+                    }
+                }
+                ("easing", _) => {
+                    if let Some(easing) = label().and_then(|v| serde_json::from_value(v).ok()) {
+                        params.easing = easing;
+                    }
+                }
+                ("angle", ParameterValue::Number(n)) => params.angle = Some(*n),
+                ("softness", ParameterValue::Number(n)) => params.softness = *n,
+                ("borderWidth", ParameterValue::Number(n)) => params.border_width = *n,
+                ("borderColor", ParameterValue::Color(c)) => params.border_color = *c,
+                ("centerX", ParameterValue::Number(n)) => params.center_x = *n,
+                ("centerY", ParameterValue::Number(n)) => params.center_y = *n,
+                _ => {}
+            }
+        }
+        params
+    }
+}
+
+/// An `enum` parameter labelled with the serde names of `value` and `options`.
+fn choice<T: Serialize>(name: &str, value: T, options: &[T]) -> EffectParameter {
+    let label = |v: &T| {
+        serde_json::to_value(v)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default()
+    };
+    EffectParameter::choice(name, label(&value), options.iter().map(label).collect())
+}
+
+/// Serialized in the TS schema's shape, which has no `inPoint`; loading
+/// one without it centres the transition on the edit point.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", from = "TransitionFields")]
 pub struct Transition {
     pub id: Uuid,
+    pub name: String,
+    #[serde(rename = "transitionType")]
     pub kind: TransitionType,
     pub duration: RationalTime,
+    /// How far before the edit point the transition starts. The remaining
+    /// `duration - in_point` plays after the edit point.
     pub in_point: RationalTime,
+    pub parameters: TransitionParameters,
+    pub from_clip_id: Option<Uuid>,
+    pub to_clip_id: Option<Uuid>,
+}
+
+/// What [`Transition`] loads from, also accepting the snake_case names
+/// projects were saved with before.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransitionFields {
+    id: Uuid,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "transitionType", alias = "kind")]
+    kind: TransitionType,
+    duration: RationalTime,
+    #[serde(default, alias = "in_point")]
+    in_point: Option<RationalTime>,
+    #[serde(default)]
+    parameters: TransitionParameters,
+    #[serde(default, alias = "from_clip_id")]
+    from_clip_id: Option<Uuid>,
+    #[serde(default, alias = "to_clip_id")]
+    to_clip_id: Option<Uuid>,
+}
+
+impl From<TransitionFields> for Transition {
+    fn from(fields: TransitionFields) -> Self {
+        let centred = RationalTime::new(fields.duration.value / 2, fields.duration.rate);
+        Self {
+            id: fields.id,
+            name: fields.name,
+            kind: fields.kind,
+            duration: fields.duration,
+            in_point: fields.in_point.unwrap_or(centred),
+            parameters: fields.parameters,
+            from_clip_id: fields.from_clip_id,
+            to_clip_id: fields.to_clip_id,
+        }
+    }
 }
 
 impl Transition {
     pub fn new(kind: TransitionType, duration: RationalTime, in_point: RationalTime) -> Self {
         Self {
             id: Uuid::new_v4(),
+            name: String::new(),
             kind,
             duration,
             in_point,
+            parameters: TransitionParameters::default(),
+            from_clip_id: None,
+            to_clip_id: None,
         }
     }
 
+    /// A hard cut. Transitions take their place from the clips they join,
+    /// so only the rate of `at_time` is used.
     pub fn cut(at_time: RationalTime) -> Self {
-        Self::new(TransitionType::Cut, RationalTime::new(0, at_time.rate), at_time)
+        let zero = RationalTime::new(0, at_time.rate);
+        Self::new(TransitionType::Cut, zero, zero)
     }
 
     pub fn cross_dissolve(duration: RationalTime, in_point: RationalTime) -> Self {
         Self::new(TransitionType::CrossDissolve, duration, in_point)
     }
+
+    pub fn wipe(duration: RationalTime, in_point: RationalTime, direction: Direction) -> Self {
+        Self::new(TransitionType::Wipe, duration, in_point).with_parameters(TransitionParameters {
+            direction,
+            ..TransitionParameters::default()
+        })
+    }
+
+    pub fn push(duration: RationalTime, in_point: RationalTime, direction: Direction) -> Self {
+        Self::new(TransitionType::Push, duration, in_point).with_parameters(TransitionParameters {
+            direction,
+            ..TransitionParameters::default()
+        })
+    }
+
+    pub fn with_parameters(mut self, parameters: TransitionParameters) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    /// Eased progress `0..=1` at `offset` from the transition's start.
+    pub fn progress_at(&self, offset: RationalTime) -> f64 {
+        if self.duration.value <= 0 {
+            return 1.0;
+        }
+        self.parameters.easing.apply(offset.to_seconds() / self.duration.to_seconds())
+    }
+
+    /// The part of the transition that plays after the edit point.
+    pub fn out_point(&self) -> RationalTime {
+        self.duration - self.in_point
+    }
+}
+
+impl Track {
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    pub fn transition_between(&self, from_clip_id: Uuid, to_clip_id: Uuid) -> Option<&Transition> {
+        self.transitions
+            .iter()
+            .find(|t| t.from_clip_id == Some(from_clip_id) && t.to_clip_id == Some(to_clip_id))
+    }
+
+    /// Places `transition` on the edit point between two adjacent clips,
+    /// replacing any transition already there.
+    pub fn add_transition(&mut self, from_clip_id: Uuid, to_clip_id: Uuid, mut transition: Transition) -> Result<Uuid, TimelineError> {
+        if self.locked {
+            return Err(TimelineError::TrackLocked(self.id));
+        }
+        let from = self.clip(from_clip_id).ok_or(TimelineError::ClipNotFound(from_clip_id))?;
+        let to = self.clip(to_clip_id).ok_or(TimelineError::ClipNotFound(to_clip_id))?;
+        if from.timeline_range.end() != to.timeline_range.start {
+            return Err(TimelineError::InvalidState(format!(
+                "clips {} and {} do not share an edit point",
+                from_clip_id, to_clip_id
+            )));
+        }
+        check_handles(from, to, &transition)?;
+
+        transition.from_clip_id = Some(from_clip_id);
+        transition.to_clip_id = Some(to_clip_id);
+        let id = transition.id;
+        self.transitions.retain(|t| t.from_clip_id != Some(from_clip_id) || t.to_clip_id != Some(to_clip_id));
+        self.transitions.push(transition);
+        Ok(id)
+    }
+
+    pub fn remove_transition(&mut self, transition_id: Uuid) -> Option<Transition> {
+        let pos = self.transitions.iter().position(|t| t.id == transition_id)?;
+        Some(self.transitions.remove(pos))
+    }
+
+    /// Brings transitions in line with the clips after an edit. A transition
+    /// follows its outgoing clip if that clip was split, is dropped once its
+    /// clips no longer meet, and fails the edit if a clip no longer has
+    /// enough handle media to cover it.
+    pub(crate) fn reconcile_transitions(&mut self, previous: &[Clip]) -> Result<(), TimelineError> {
+        let mut kept = Vec::with_capacity(self.transitions.len());
+        for mut transition in std::mem::take(&mut self.transitions) {
+            let (Some(from_id), Some(to_id)) = (transition.from_clip_id, transition.to_clip_id) else {
+                continue;
+            };
+            let Some(to) = self.clip(to_id) else { continue };
+            let from = match self.clip(from_id) {
+                Some(from) if from.timeline_range.end() == to.timeline_range.start => Some(from),
+                _ => previous.iter().find(|c| c.id == from_id).and_then(|old| {
+                    self.clips.iter().find(|c| {
+                        c.timeline_range.end() == to.timeline_range.start
+                            && c.source == old.source
+                            && last_source_frame(c) == last_source_frame(old)
+                    })
+                }),
+            };
+            let Some(from) = from else { continue };
+
+            check_handles(from, to, &transition)?;
+            transition.from_clip_id = Some(from.id);
+            kept.push(transition);
+        }
+        self.transitions = kept;
+        Ok(())
+    }
+}
+
+impl Timeline {
+    pub fn add_transition(&mut self, track_id: Uuid, from_clip_id: Uuid, to_clip_id: Uuid, transition: Transition) -> Result<Uuid, TimelineError> {
+        self.track_mut(track_id)
+            .ok_or(TimelineError::TrackNotFound(track_id))?
+            .add_transition(from_clip_id, to_clip_id, transition)
+    }
+}
+
+/// Checks that the outgoing clip has media past its out point and the
+/// incoming clip has media before its in point to cover the overlap.
+fn check_handles(from: &Clip, to: &Clip, transition: &Transition) -> Result<(), TimelineError> {
+    let before = transition.in_point;
+    let after = transition.out_point();
+    if before.value < 0 || after.value < 0 || before > from.timeline_range.duration || after > to.timeline_range.duration {
+        return Err(TimelineError::InvalidTimeRange {
+            start: -before.value,
+            end: after.value,
+        });
+    }
+
+    for (clip, edge, extension) in [(to, TrimEdge::Start, before), (from, TrimEdge::End, after)] {
+        if let Some((required, available)) = clip.handle(edge, extension) {
+            if required > available {
+                return Err(handle_error(clip, required, available));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn last_source_frame(clip: &Clip) -> RationalTime {
+    let end = clip.timeline_range.end();
+    clip.source_time_at(&(end - RationalTime::new(1, end.rate)))
+}
+
+fn handle_error(clip: &Clip, required: RationalTime, available: RationalTime) -> TimelineError {
+    let rate = clip.source_range.start.rate;
+    TimelineError::InsufficientHandles {
+        clip_id: clip.id,
+        required: required.rescaled(rate).value,
+        available: available.rescaled(rate).value.max(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MediaMetadata, MediaSource, TimeRange, TrackKind};
+
+    fn frames(value: i64) -> RationalTime {
+        RationalTime::new(value, 24)
+    }
+
+    fn range(start: i64, end: i64) -> TimeRange {
+        TimeRange::from_start_end(frames(start), frames(end))
+    }
+
+    /// Two back-to-back clips of 96 frames, each with 48 frames of handles
+    /// on both sides, and a third one after a gap.
+    fn track() -> (Track, [Uuid; 3]) {
+        let media = MediaSource::new("shot.mov").with_metadata(MediaMetadata { duration: frames(192), ..MediaMetadata::default() });
+        let mut track = Track::new("V1", TrackKind::Video);
+        let mut ids = [Uuid::nil(); 3];
+        for (id, start) in ids.iter_mut().zip([0, 96, 240]) {
+            let clip = Clip::new("shot", media.clone(), range(48, 144), range(start, start + 96));
+            *id = clip.id;
+            track.add_clip(clip).unwrap();
+        }
+        (track, ids)
+    }
+
+    #[test]
+    fn cuts_need_no_handles() {
+        let mut track = Track::new("V1", TrackKind::Video);
+        let a = Clip::new("a", "a.mov", range(0, 24), range(0, 24));
+        let b = Clip::new("b", "b.mov", range(0, 24), range(24, 48));
+        let (a_id, b_id) = (a.id, b.id);
+        track.add_clip(a).unwrap();
+        track.add_clip(b).unwrap();
+
+        let cut = Transition::cut(frames(24));
+        assert_eq!((cut.duration, cut.in_point), (frames(0), frames(0)));
+        track.add_transition(a_id, b_id, cut).unwrap();
+        assert_eq!(track.transition_between(a_id, b_id).unwrap().kind, TransitionType::Cut);
+    }
+
+    #[test]
+    fn handles_must_cover_both_sides() {
+        let (mut track, [a, b, c]) = track();
+        track.add_transition(a, b, Transition::cross_dissolve(frames(96), frames(48))).unwrap();
+
+        let result = track.add_transition(a, b, Transition::cross_dissolve(frames(100), frames(50)));
+        assert!(matches!(result, Err(TimelineError::InsufficientHandles { clip_id, required: 50, available: 48 }) if clip_id == b));
+        let result = track.add_transition(a, b, Transition::cross_dissolve(frames(100), frames(40)));
+        assert!(matches!(result, Err(TimelineError::InsufficientHandles { clip_id, required: 60, available: 48 }) if clip_id == a));
+        let result = track.add_transition(a, b, Transition::cross_dissolve(frames(24), frames(30)));
+        assert!(matches!(result, Err(TimelineError::InvalidTimeRange { .. })));
+        let result = track.add_transition(b, c, Transition::cross_dissolve(frames(24), frames(12)));
+        assert!(matches!(result, Err(TimelineError::InvalidState(_))));
+
+        // The failed attempts leave the first transition in place.
+        assert_eq!(track.transitions().len(), 1);
+        assert_eq!(track.transition_between(a, b).unwrap().duration, frames(96));
+    }
+
+    #[test]
+    fn transitions_follow_edits_to_their_clips() {
+        let (mut track, [a, b, _]) = track();
+        let id = track.add_transition(a, b, Transition::cross_dissolve(frames(24), frames(12))).unwrap();
+
+        // Splitting the outgoing clip hands the transition to its tail.
+        let tail = track.split_clip(a, frames(48)).unwrap();
+        assert_eq!(track.transition_between(tail, b).map(|t| t.id), Some(id));
+
+        // Slipping the incoming clip into its head handle is refused.
+        let result = track.slip(b, frames(-40));
+        assert!(matches!(result, Err(TimelineError::InsufficientHandles { .. })));
+        assert_eq!(track.clip(b).unwrap().source_range, range(48, 144));
+
+        // Clips that no longer meet lose their transition.
+        track.move_clip(b, frames(24)).unwrap();
+        assert!(track.transitions().is_empty());
+    }
+
+    #[test]
+    fn transitions_go_with_deleted_clips() {
+        let (mut track, [a, b, _]) = track();
+        track.add_transition(a, b, Transition::cross_dissolve(frames(24), frames(12))).unwrap();
+        let mut dropped = track.clone();
+
+        track.remove_clip(b).unwrap();
+        assert!(track.transitions().is_empty());
+
+        // Clips can also leave without going through `remove_clip`, as
+        // when history replays a batch of commands.
+        let previous = dropped.clips.clone();
+        dropped.clips.retain(|clip| clip.id != a);
+        dropped.reconcile_transitions(&previous).unwrap();
+        assert!(dropped.transitions().is_empty());
+    }
+
+    #[test]
+    fn transitions_round_trip_the_ts_schema() {
+        let from = Uuid::new_v4();
+        let to = Uuid::new_v4();
+        let fixture = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "name": "Dissolve",
+            "transitionType": "cross-dissolve",
+            "duration": { "value": 12, "rate": 24 },
+            "fromClipId": from,
+            "toClipId": to,
+        });
+        let transition: Transition = serde_json::from_value(fixture.clone()).unwrap();
+        assert_eq!(transition.kind, TransitionType::CrossDissolve);
+        assert_eq!(transition.in_point, frames(6));
+        assert_eq!((transition.from_clip_id, transition.to_clip_id), (Some(from), Some(to)));
+
+        let saved = serde_json::to_value(&transition).unwrap();
+        for key in ["id", "name", "transitionType", "duration", "fromClipId", "toClipId"] {
+            assert_eq!(saved[key], fixture[key], "{key}");
+        }
+        assert_eq!(serde_json::from_value::<Transition>(saved).unwrap(), transition);
+    }
+
+    #[test]
+    fn transition_types_use_the_ts_names() {
+        let names: Vec<_> = [TransitionType::CrossDissolve, TransitionType::DipToBlack, TransitionType::Wipe, TransitionType::Cut]
+            .iter()
+            .map(|kind| serde_json::to_value(kind).unwrap())
+            .collect();
+        assert_eq!(names, ["cross-dissolve", "dip", "wipe", "cut"]);
+        let fade: TransitionType = serde_json::from_str("\"fade\"").unwrap();
+        assert_eq!(fade, TransitionType::DipToBlack);
+    }
+
+    #[test]
+    fn transitions_saved_with_the_old_names_still_load() {
+        let from = Uuid::new_v4();
+        let old = serde_json::json!({
+            "id": Uuid::new_v4(),
+            "kind": "DipToBlack",
+            "duration": { "value": 24, "rate": 24 },
+            "in_point": { "value": 0, "rate": 24 },
+            "from_clip_id": from,
+            "to_clip_id": null,
+        });
+        let transition: Transition = serde_json::from_value(old).unwrap();
+        assert_eq!(transition.kind, TransitionType::DipToBlack);
+        assert_eq!(transition.in_point, frames(0));
+        assert_eq!(transition.from_clip_id, Some(from));
+        assert_eq!(transition.to_clip_id, None);
+    }
 }
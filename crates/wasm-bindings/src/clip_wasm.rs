@@ -1,6 +1,5 @@
 use wasm_bindgen::prelude::*;
-use serde_wasm_bindgen;
-use timeline_core::{Clip, TimeRange};
+use timeline_core::{Clip, RationalTime, TimeRange};
 
 #[wasm_bindgen]
 pub fn create_clip(name: String, source_path: String, source_range: JsValue, timeline_range: JsValue) -> JsValue {
@@ -16,9 +15,24 @@ pub fn create_clip(name: String, source_path: String, source_range: JsValue, tim
     serde_wasm_bindgen::to_value(&clip).unwrap_or(JsValue::NULL)
 }
 
+/// `clip` trimmed to start `new_start` seconds into the timeline and last
+/// `new_duration` seconds, or `None` if the trim is not possible. Times
+/// from JS go through the checked forms so out of range input cannot
+/// overflow inside the trim.
+fn trimmed(mut clip: Clip, new_start: f64, new_duration: f64) -> Option<Clip> {
+    let rate = clip.timeline_range.start.rate;
+    let new_start = RationalTime::checked_from_seconds(new_start, rate).ok()?;
+    let new_duration = RationalTime::checked_from_seconds(new_duration, rate).ok()?;
+    let new_end = new_start.checked_add(&new_duration).ok()?;
+    new_start.checked_sub(&clip.timeline_range.start).ok()?;
+    clip.timeline_range.checked_end().ok()?.checked_sub(&new_end).ok()?;
+    clip.trim(new_start, new_duration).ok()?;
+    Some(clip)
+}
+
 #[wasm_bindgen]
 pub fn trim_clip(clip: JsValue, new_start: JsValue, new_duration: JsValue) -> JsValue {
-    let mut clip: Clip = match serde_wasm_bindgen::from_value(clip) {
+    let clip: Clip = match serde_wasm_bindgen::from_value(clip) {
         Ok(c) => c,
         Err(_) => return JsValue::NULL,
     };
@@ -30,6 +44,42 @@ pub fn trim_clip(clip: JsValue, new_start: JsValue, new_duration: JsValue) -> Js
         Ok(d) => d,
         Err(_) => return JsValue::NULL,
     };
-    clip.trim(new_start, new_duration);
-    serde_wasm_bindgen::to_value(&clip).unwrap_or(JsValue::NULL)
+    match trimmed(clip, new_start, new_duration) {
+        Some(clip) => serde_wasm_bindgen::to_value(&clip).unwrap_or(JsValue::NULL),
+        None => JsValue::NULL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip() -> Clip {
+        let range = |start, duration| TimeRange::new(RationalTime::new(start, 24), RationalTime::new(duration, 24));
+        Clip::new("shot", "shot.mov", range(48, 240), range(240, 240))
+    }
+
+    #[test]
+    fn trim_moves_the_source_with_the_head() {
+        let clip = trimmed(clip(), 11.0, 4.0).unwrap();
+        assert_eq!(clip.timeline_range.start, RationalTime::new(264, 24));
+        assert_eq!(clip.timeline_range.duration, RationalTime::new(96, 24));
+        assert_eq!(clip.source_range.start, RationalTime::new(72, 24));
+        assert_eq!(clip.source_range.duration, RationalTime::new(96, 24));
+    }
+
+    #[test]
+    fn impossible_trims_return_nothing() {
+        assert!(trimmed(clip(), 11.0, 0.0).is_none());
+        // Three seconds earlier would need a second of media before the
+        // source starts.
+        assert!(trimmed(clip(), 7.0, 4.0).is_none());
+    }
+
+    #[test]
+    fn unrepresentable_trims_return_nothing() {
+        assert!(trimmed(clip(), f64::NAN, 4.0).is_none());
+        assert!(trimmed(clip(), 11.0, f64::INFINITY).is_none());
+        assert!(trimmed(clip(), 1e300, 4.0).is_none());
+    }
 }
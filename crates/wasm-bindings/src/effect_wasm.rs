@@ -0,0 +1,17 @@
+use wasm_bindgen::prelude::*;
+use timeline_core::{EffectParameter, RationalTime};
+
+/// Evaluates a keyframed parameter at an exact time so the preview matches
+/// the native renderer.
+#[wasm_bindgen]
+pub fn evaluate_parameter(parameter: JsValue, time: JsValue) -> JsValue {
+    let parameter: EffectParameter = match serde_wasm_bindgen::from_value(parameter) {
+        Ok(p) => p,
+        Err(_) => return JsValue::NULL,
+    };
+    let time: RationalTime = match serde_wasm_bindgen::from_value(time) {
+        Ok(t) => t,
+        Err(_) => return JsValue::NULL,
+    };
+    serde_wasm_bindgen::to_value(&parameter.value_at(&time)).unwrap_or(JsValue::NULL)
+}
@@ -1,6 +1,10 @@
 use wasm_bindgen::prelude::*;
 
 mod clip_wasm;
+mod effect_wasm;
+mod snap_wasm;
+mod time_range_wasm;
+mod timecode_wasm;
 mod timeline_wasm;
 mod track_wasm;
 
@@ -16,5 +20,9 @@ extern "C" {
 }
 
 pub use clip_wasm::*;
+pub use effect_wasm::*;
+pub use snap_wasm::*;
+pub use time_range_wasm::*;
+pub use timecode_wasm::*;
 pub use timeline_wasm::*;
 pub use track_wasm::*;
@@ -0,0 +1,54 @@
+use wasm_bindgen::prelude::*;
+use timeline_core::{RationalTime, SnapContext, SnapOptions, Timeline};
+
+fn parse_inputs(timeline: JsValue, options: JsValue) -> Result<(Timeline, SnapOptions), JsValue> {
+    let timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
+        .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
+    let options: SnapOptions = serde_wasm_bindgen::from_value(options)
+        .map_err(|e| JsValue::from_str(&format!("Snap options error: {}", e)))?;
+    Ok((timeline, options))
+}
+
+/// Snap targets for one drag. Build it when the drag starts and call
+/// `snap` on every move, so the timeline is only read once.
+#[wasm_bindgen]
+pub struct SnapSession {
+    context: SnapContext,
+}
+
+#[wasm_bindgen]
+impl SnapSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(timeline: JsValue, options: JsValue) -> Result<SnapSession, JsValue> {
+        let (timeline, options) = parse_inputs(timeline, options)?;
+        Ok(SnapSession { context: timeline.snap_context(&options) })
+    }
+
+    /// Rounds a dragged time in seconds to the nearest timeline frame and
+    /// returns the target it snaps to, or `null`.
+    pub fn snap(&self, time: f64) -> Result<JsValue, JsValue> {
+        let time = RationalTime::checked_from_seconds(time, self.context.frame_rate())
+            .map_err(|e| JsValue::from_str(&format!("Time error: {}", e)))?;
+        serde_wasm_bindgen::to_value(&self.context.snap(&time))
+            .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+    }
+}
+
+/// Rounds a dragged time in seconds to the nearest timeline frame and
+/// returns the target it snaps to, or `null`. Reads the whole timeline on
+/// each call; use [`SnapSession`] while dragging.
+#[wasm_bindgen]
+pub fn snap_time(timeline: JsValue, time: f64, options: JsValue) -> Result<JsValue, JsValue> {
+    let (timeline, options) = parse_inputs(timeline, options)?;
+    let time = RationalTime::checked_from_seconds(time, timeline.metadata.frame_rate)
+        .map_err(|e| JsValue::from_str(&format!("Time error: {}", e)))?;
+    serde_wasm_bindgen::to_value(&timeline.snap(&time, &options))
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn get_snap_targets(timeline: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    let (timeline, options) = parse_inputs(timeline, options)?;
+    serde_wasm_bindgen::to_value(&timeline.snap_targets(&options))
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
@@ -0,0 +1,77 @@
+use wasm_bindgen::prelude::*;
+use serde::de::DeserializeOwned;
+use timeline_core::{RationalTime, TimeRange};
+
+// Ranges come from JS with any rates, so only the checked forms are used:
+// rates with no common `u32` base throw instead of panicking.
+
+fn from_js<T: DeserializeOwned>(value: JsValue, label: &str) -> Result<T, JsError> {
+    serde_wasm_bindgen::from_value(value)
+        .map_err(|e| JsError::new(&format!("{} error: {}", label, e)))
+}
+
+fn to_js<T: serde::Serialize>(value: &T) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value)
+        .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn time_range_is_empty(range: JsValue) -> Result<bool, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    Ok(range.is_empty())
+}
+
+#[wasm_bindgen]
+pub fn time_range_intersection(a: JsValue, b: JsValue) -> Result<JsValue, JsError> {
+    let a: TimeRange = from_js(a, "Range")?;
+    let b: TimeRange = from_js(b, "Range")?;
+    match a.checked_intersection(&b)? {
+        Some(range) => to_js(&range),
+        None => Ok(JsValue::NULL),
+    }
+}
+
+#[wasm_bindgen]
+pub fn time_range_union_hull(a: JsValue, b: JsValue) -> Result<JsValue, JsError> {
+    let a: TimeRange = from_js(a, "Range")?;
+    let b: TimeRange = from_js(b, "Range")?;
+    to_js(&a.checked_union_hull(&b)?)
+}
+
+#[wasm_bindgen]
+pub fn time_range_clamped_to(range: JsValue, bounds: JsValue) -> Result<JsValue, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    let bounds: TimeRange = from_js(bounds, "Bounds")?;
+    to_js(&range.checked_clamped_to(&bounds)?)
+}
+
+#[wasm_bindgen]
+pub fn time_range_split_at(range: JsValue, time: JsValue) -> Result<JsValue, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    let time: RationalTime = from_js(time, "Time")?;
+    match range.checked_split_at(&time)? {
+        Some(parts) => to_js(&parts),
+        None => Ok(JsValue::NULL),
+    }
+}
+
+#[wasm_bindgen]
+pub fn time_range_subtract(range: JsValue, other: JsValue) -> Result<JsValue, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    let other: TimeRange = from_js(other, "Range")?;
+    to_js(&range.checked_subtract(&other)?)
+}
+
+#[wasm_bindgen]
+pub fn time_range_extended_by(range: JsValue, amount: JsValue) -> Result<JsValue, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    let amount: RationalTime = from_js(amount, "Amount")?;
+    to_js(&range.checked_extended_by(amount)?)
+}
+
+#[wasm_bindgen]
+pub fn time_range_shifted_by(range: JsValue, offset: JsValue) -> Result<JsValue, JsError> {
+    let range: TimeRange = from_js(range, "Range")?;
+    let offset: RationalTime = from_js(offset, "Offset")?;
+    to_js(&range.checked_shifted_by(offset)?)
+}
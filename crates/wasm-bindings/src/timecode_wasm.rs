@@ -0,0 +1,23 @@
+use wasm_bindgen::prelude::*;
+use timeline_core::{FrameRate, RationalTime, Timecode, TimecodeWrap};
+
+#[wasm_bindgen]
+pub fn format_timecode(time: JsValue, rate: JsValue, drop_frame: bool) -> Result<String, JsValue> {
+    let time: RationalTime = serde_wasm_bindgen::from_value(time)
+        .map_err(|e| JsValue::from_str(&format!("Time error: {}", e)))?;
+    let rate: FrameRate = serde_wasm_bindgen::from_value(rate)
+        .map_err(|e| JsValue::from_str(&format!("Frame rate error: {}", e)))?;
+    let timecode = Timecode::from_time(&time, rate, drop_frame, TimecodeWrap::Extend)
+        .map_err(|e| JsValue::from_str(&format!("Timecode error: {}", e)))?;
+    Ok(timecode.to_string())
+}
+
+#[wasm_bindgen]
+pub fn parse_timecode(timecode: String, rate: JsValue) -> Result<JsValue, JsValue> {
+    let rate: FrameRate = serde_wasm_bindgen::from_value(rate)
+        .map_err(|e| JsValue::from_str(&format!("Frame rate error: {}", e)))?;
+    let timecode = Timecode::parse(&timecode, rate, TimecodeWrap::Extend)
+        .map_err(|e| JsValue::from_str(&format!("Timecode error: {}", e)))?;
+    serde_wasm_bindgen::to_value(&timecode.to_time())
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
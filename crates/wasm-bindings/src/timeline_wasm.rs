@@ -1,11 +1,82 @@
 use wasm_bindgen::prelude::*;
-use serde_wasm_bindgen;
-use timeline_core::{Timeline, TimelineTrack, Clip, TimeRange};
+use uuid::Uuid;
+use timeline_core::{Clip, FrameRate, RationalTime, Timeline, TimelineError, Track};
+
+// The edits below are split from their bindings so they can be tested
+// without a JavaScript host.
+
+fn parse_id(id: &str, label: &str) -> Result<Uuid, String> {
+    Uuid::parse_str(id).map_err(|e| format!("{} id error: {}", label, e))
+}
+
+fn new_timeline(name: String, numerator: u32, denominator: u32) -> Result<Timeline, String> {
+    let frame_rate = FrameRate::try_new(numerator, denominator).map_err(|e| format!("Frame rate error: {}", e))?;
+    Ok(Timeline::new(name, frame_rate))
+}
+
+fn insert_clip(timeline: &mut Timeline, track_id: &str, clip: Clip) -> Result<(), String> {
+    let track_id = parse_id(track_id, "Track")?;
+    timeline.add_clip_to_track(track_id, clip)
+        .map_err(|e| format!("Add clip error: {}", e))
+}
+
+fn delete_clip(timeline: &mut Timeline, track_id: &str, clip_id: &str) -> Result<(), String> {
+    let track_id = parse_id(track_id, "Track")?;
+    let clip_id = parse_id(clip_id, "Clip")?;
+    timeline.remove_clip(track_id, clip_id)
+        .map(|_| ())
+        .map_err(|e| format!("Remove clip error: {}", e))
+}
+
+/// Seconds from JS as a time at the timeline's rate, failing instead of
+/// saturating when they are out of range.
+fn time_from_seconds(timeline: &Timeline, seconds: f64, label: &str) -> Result<RationalTime, String> {
+    RationalTime::checked_from_seconds(seconds, timeline.metadata.frame_rate).map_err(|e| format!("{} error: {}", label, e))
+}
+
+fn cut_clip(timeline: &mut Timeline, track_id: &str, clip_id: &str, position: f64) -> Result<(), String> {
+    let track_id = parse_id(track_id, "Track")?;
+    let clip_id = parse_id(clip_id, "Clip")?;
+    let position = time_from_seconds(timeline, position, "Position")?;
+    timeline.split_clip(track_id, clip_id, position)
+        .map(|_| ())
+        .map_err(|e| format!("Split clip error: {}", e))
+}
+
+/// Creates an empty timeline running at `frame_rate_numerator /
+/// frame_rate_denominator` frames per second, e.g. `24, 1` or `30000, 1001`
+/// for 29.97.
+///
+/// Breaking change: this used to take a single whole-number rate and
+/// return `null` when it failed. It now needs both parts of the rate and
+/// throws an `Error` for a zero numerator or denominator.
+/// Moves a clip and the clips linked to it by `delta` seconds, checking
+/// that none of them would move out of range first.
+fn shift_clip(timeline: &mut Timeline, track_id: &str, clip_id: &str, delta: f64) -> Result<(), String> {
+    let track_id = parse_id(track_id, "Track")?;
+    let clip_id = parse_id(clip_id, "Clip")?;
+    let delta = time_from_seconds(timeline, delta, "Delta")?;
+    let (_, clip) = timeline.find_clip(clip_id).ok_or_else(|| format!("Move clip error: {}", TimelineError::ClipNotFound(clip_id)))?;
+    let moved = match clip.link {
+        Some(link) => timeline.linked_clips(link.group_id).into_iter().map(|(_, c)| c).collect(),
+        None => vec![clip],
+    };
+    for clip in moved {
+        clip.timeline_range
+            .checked_shifted_by(delta)
+            .and_then(|range| range.checked_end())
+            .map_err(|e| format!("Delta error: {}", e))?;
+    }
+    timeline.move_clip(track_id, clip_id, delta)
+        .map(|_| ())
+        .map_err(|e| format!("Move clip error: {}", e))
+}
 
 #[wasm_bindgen]
-pub fn create_timeline(name: String, frame_rate: u32) -> JsValue {
-    let timeline = Timeline::new(name, frame_rate);
-    serde_wasm_bindgen::to_value(&timeline).unwrap_or(JsValue::NULL)
+pub fn create_timeline(name: String, frame_rate_numerator: u32, frame_rate_denominator: u32) -> Result<JsValue, JsValue> {
+    let timeline = new_timeline(name, frame_rate_numerator, frame_rate_denominator)?;
+    serde_wasm_bindgen::to_value(&timeline)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
 
 #[wasm_bindgen]
@@ -28,7 +99,7 @@ pub fn serialize_timeline(timeline: JsValue) -> Result<String, JsValue> {
 pub fn add_track_to_timeline(timeline: JsValue, track: JsValue) -> Result<JsValue, JsValue> {
     let mut timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
         .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
-    let track: TimelineTrack = serde_wasm_bindgen::from_value(track)
+    let track: Track = serde_wasm_bindgen::from_value(track)
         .map_err(|e| JsValue::from_str(&format!("Track error: {}", e)))?;
     timeline.add_track(track);
     serde_wasm_bindgen::to_value(&timeline)
@@ -41,8 +112,7 @@ pub fn add_clip_to_track(timeline: JsValue, track_id: String, clip: JsValue) ->
         .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
     let clip: Clip = serde_wasm_bindgen::from_value(clip)
         .map_err(|e| JsValue::from_str(&format!("Clip error: {}", e)))?;
-    timeline.add_clip_to_track(&track_id, clip)
-        .map_err(|e| JsValue::from_str(&format!("Add clip error: {}", e)))?;
+    insert_clip(&mut timeline, &track_id, clip)?;
     serde_wasm_bindgen::to_value(&timeline)
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
@@ -51,8 +121,7 @@ pub fn add_clip_to_track(timeline: JsValue, track_id: String, clip: JsValue) ->
 pub fn remove_clip(timeline: JsValue, track_id: String, clip_id: String) -> Result<JsValue, JsValue> {
     let mut timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
         .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
-    timeline.remove_clip(&track_id, &clip_id)
-        .map_err(|e| JsValue::from_str(&format!("Remove clip error: {}", e)))?;
+    delete_clip(&mut timeline, &track_id, &clip_id)?;
     serde_wasm_bindgen::to_value(&timeline)
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
@@ -63,8 +132,7 @@ pub fn split_clip(timeline: JsValue, track_id: String, clip_id: String, position
         .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
     let position: f64 = serde_wasm_bindgen::from_value(position)
         .map_err(|e| JsValue::from_str(&format!("Position error: {}", e)))?;
-    timeline.split_clip(&track_id, &clip_id, position)
-        .map_err(|e| JsValue::from_str(&format!("Split clip error: {}", e)))?;
+    cut_clip(&mut timeline, &track_id, &clip_id, position)?;
     serde_wasm_bindgen::to_value(&timeline)
         .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
 }
@@ -75,5 +143,185 @@ pub fn get_timeline_duration(timeline: JsValue) -> JsValue {
         Ok(t) => t,
         Err(_) => return JsValue::from_f64(0.0),
     };
-    JsValue::from_f64(timeline.duration())
+    JsValue::from_f64(timeline.duration().to_seconds())
+}
+
+#[wasm_bindgen]
+pub fn link_clips(timeline: JsValue, clip_ids: JsValue) -> Result<JsValue, JsValue> {
+    let mut timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
+        .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
+    let clip_ids: Vec<String> = serde_wasm_bindgen::from_value(clip_ids)
+        .map_err(|e| JsValue::from_str(&format!("Clip ids error: {}", e)))?;
+    let clip_ids = clip_ids
+        .iter()
+        .map(|id| parse_id(id, "Clip"))
+        .collect::<Result<Vec<_>, _>>()?;
+    timeline.link_clips(&clip_ids)
+        .map_err(|e| JsValue::from_str(&format!("Link clips error: {}", e)))?;
+    serde_wasm_bindgen::to_value(&timeline)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn unlink_clips(timeline: JsValue, group_id: String) -> Result<JsValue, JsValue> {
+    let mut timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
+        .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
+    let group_id = parse_id(&group_id, "Link group")?;
+    timeline.unlink_clips(group_id);
+    serde_wasm_bindgen::to_value(&timeline)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn move_clip(timeline: JsValue, track_id: String, clip_id: String, delta: JsValue) -> Result<JsValue, JsValue> {
+    let mut timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
+        .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
+    let delta: f64 = serde_wasm_bindgen::from_value(delta)
+        .map_err(|e| JsValue::from_str(&format!("Delta error: {}", e)))?;
+    shift_clip(&mut timeline, &track_id, &clip_id, delta)?;
+    serde_wasm_bindgen::to_value(&timeline)
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[wasm_bindgen]
+pub fn get_sync_offsets(timeline: JsValue) -> Result<JsValue, JsValue> {
+    let timeline: Timeline = serde_wasm_bindgen::from_value(timeline)
+        .map_err(|e| JsValue::from_str(&format!("Timeline error: {}", e)))?;
+    serde_wasm_bindgen::to_value(&timeline.sync_offsets())
+        .map_err(|e| JsValue::from_str(&format!("Serialization error: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use timeline_core::{TimeRange, TrackKind};
+
+    fn seconds(value: i64) -> RationalTime {
+        RationalTime::new(value * 24, 24)
+    }
+
+    fn clip(start: i64, duration: i64) -> Clip {
+        let source = TimeRange::new(seconds(0), seconds(duration));
+        Clip::new("shot", "shot.mov", source, TimeRange::new(seconds(start), seconds(duration)))
+    }
+
+    fn timeline() -> (Timeline, String) {
+        let mut timeline = new_timeline("edit".to_string(), 24, 1).unwrap();
+        let track = Track::new("V1", TrackKind::Video);
+        let track_id = track.id.to_string();
+        timeline.add_track(track);
+        (timeline, track_id)
+    }
+
+    #[test]
+    fn create_timeline_takes_fractional_rates() {
+        assert_eq!(new_timeline("edit".to_string(), 25, 1).unwrap().metadata.frame_rate, FrameRate::from(25));
+        assert_eq!(new_timeline("edit".to_string(), 30000, 1001).unwrap().metadata.frame_rate, FrameRate::FPS_29_97);
+        assert_eq!(new_timeline("edit".to_string(), 48000, 2002).unwrap().metadata.frame_rate, FrameRate::FPS_23_976);
+    }
+
+    #[test]
+    fn create_timeline_rejects_a_zero_rate() {
+        assert!(new_timeline("edit".to_string(), 0, 1).unwrap_err().starts_with("Frame rate error"));
+        assert!(new_timeline("edit".to_string(), 24, 0).unwrap_err().starts_with("Frame rate error"));
+    }
+
+    #[test]
+    fn add_clip_rejects_overlaps_and_unknown_tracks() {
+        let (mut timeline, track_id) = timeline();
+        insert_clip(&mut timeline, &track_id, clip(0, 5)).unwrap();
+        insert_clip(&mut timeline, &track_id, clip(5, 5)).unwrap();
+
+        let error = insert_clip(&mut timeline, &track_id, clip(8, 5)).unwrap_err();
+        assert!(error.contains("Clip overlap"), "{}", error);
+        let error = insert_clip(&mut timeline, &uuid::Uuid::new_v4().to_string(), clip(20, 5)).unwrap_err();
+        assert!(error.contains("Track not found"), "{}", error);
+        let error = insert_clip(&mut timeline, "not-an-id", clip(20, 5)).unwrap_err();
+        assert!(error.starts_with("Track id error"), "{}", error);
+        assert_eq!(timeline.tracks[0].clips().len(), 2);
+    }
+
+    #[test]
+    fn remove_clip_leaves_unlinked_neighbours() {
+        let (mut timeline, track_id) = timeline();
+        let (first, second) = (clip(0, 5), clip(5, 5));
+        let second_id = second.id;
+        insert_clip(&mut timeline, &track_id, first.clone()).unwrap();
+        insert_clip(&mut timeline, &track_id, second).unwrap();
+
+        delete_clip(&mut timeline, &track_id, &first.id.to_string()).unwrap();
+        let ids: Vec<_> = timeline.tracks[0].clips().iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![second_id]);
+
+        let error = delete_clip(&mut timeline, &track_id, &first.id.to_string()).unwrap_err();
+        assert!(error.contains("Clip not found"), "{}", error);
+        let error = delete_clip(&mut timeline, &uuid::Uuid::new_v4().to_string(), &second_id.to_string()).unwrap_err();
+        assert!(error.contains("Track not found"), "{}", error);
+    }
+
+    #[test]
+    fn remove_clip_takes_linked_clips_with_it() {
+        let (mut timeline, track_id) = timeline();
+        let audio = Track::new("A1", TrackKind::Audio);
+        let audio_id = audio.id.to_string();
+        timeline.add_track(audio);
+        let (picture, sound) = (clip(0, 5), clip(0, 5));
+        let ids = [picture.id, sound.id];
+        insert_clip(&mut timeline, &track_id, picture).unwrap();
+        insert_clip(&mut timeline, &audio_id, sound).unwrap();
+        insert_clip(&mut timeline, &audio_id, clip(5, 5)).unwrap();
+        timeline.link_clips(&ids).unwrap();
+
+        delete_clip(&mut timeline, &track_id, &ids[0].to_string()).unwrap();
+        assert!(timeline.tracks[0].clips().is_empty());
+        assert_eq!(timeline.tracks[1].clips().len(), 1);
+        assert_ne!(timeline.tracks[1].clips()[0].id, ids[1]);
+    }
+
+    #[test]
+    fn split_clip_cuts_at_the_position() {
+        let (mut timeline, track_id) = timeline();
+        let shot = clip(2, 6);
+        let shot_id = shot.id.to_string();
+        insert_clip(&mut timeline, &track_id, shot).unwrap();
+
+        cut_clip(&mut timeline, &track_id, &shot_id, 5.0).unwrap();
+        let clips = timeline.tracks[0].clips();
+        assert_eq!(clips.len(), 2);
+        assert_eq!(clips[0].timeline_range, TimeRange::new(seconds(2), seconds(3)));
+        assert_eq!(clips[1].timeline_range, TimeRange::new(seconds(5), seconds(3)));
+        assert_eq!(clips[1].source_range.start, seconds(3));
+
+        let error = cut_clip(&mut timeline, &uuid::Uuid::new_v4().to_string(), &shot_id, 3.0).unwrap_err();
+        assert!(error.contains("Track not found"), "{}", error);
+        assert!(cut_clip(&mut timeline, &track_id, &shot_id, 20.0).is_err());
+    }
+
+    #[test]
+    fn cut_clip_rejects_unrepresentable_positions() {
+        let (mut timeline, track_id) = timeline();
+        let shot = clip(0, 5);
+        let clip_id = shot.id.to_string();
+        insert_clip(&mut timeline, &track_id, shot).unwrap();
+
+        for position in [f64::NAN, f64::INFINITY, 1e300] {
+            assert!(cut_clip(&mut timeline, &track_id, &clip_id, position).unwrap_err().starts_with("Position error"));
+        }
+        assert_eq!(timeline.tracks[0].clips().len(), 1);
+    }
+
+    #[test]
+    fn shift_clip_rejects_deltas_that_overflow() {
+        let (mut timeline, track_id) = timeline();
+        let shot = clip(0, 5);
+        let clip_id = shot.id.to_string();
+        insert_clip(&mut timeline, &track_id, shot).unwrap();
+
+        assert!(shift_clip(&mut timeline, &track_id, &clip_id, f64::NAN).unwrap_err().starts_with("Delta error"));
+        assert!(shift_clip(&mut timeline, &track_id, &clip_id, 1e300).unwrap_err().starts_with("Delta error"));
+        assert_eq!(timeline.tracks[0].clips()[0].timeline_range.start, seconds(0));
+
+        shift_clip(&mut timeline, &track_id, &clip_id, 2.0).unwrap();
+        assert_eq!(timeline.tracks[0].clips()[0].timeline_range.start, seconds(2));
+    }
 }